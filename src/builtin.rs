@@ -0,0 +1,182 @@
+use crate::interpreter::{Key, RuntimeErrorKind, DATA};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Builtin {
+    LEN,
+    SUBSTRING,
+    CHAR_AT,
+    SPLIT,
+    FIND,
+    PUSH,
+    POP,
+    HAS,
+    REMOVE,
+    KEYS,
+}
+
+impl Builtin {
+    pub fn from_name(name: &str) -> Option<Builtin> {
+        match name {
+            "len" => Some(Builtin::LEN),
+            "substring" => Some(Builtin::SUBSTRING),
+            "char_at" => Some(Builtin::CHAR_AT),
+            "split" => Some(Builtin::SPLIT),
+            "find" => Some(Builtin::FIND),
+            "push" => Some(Builtin::PUSH),
+            "pop" => Some(Builtin::POP),
+            "has" => Some(Builtin::HAS),
+            "remove" => Some(Builtin::REMOVE),
+            "keys" => Some(Builtin::KEYS),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Builtin::LEN => "len",
+            Builtin::SUBSTRING => "substring",
+            Builtin::CHAR_AT => "char_at",
+            Builtin::SPLIT => "split",
+            Builtin::FIND => "find",
+            Builtin::PUSH => "push",
+            Builtin::POP => "pop",
+            Builtin::HAS => "has",
+            Builtin::REMOVE => "remove",
+            Builtin::KEYS => "keys",
+        }
+    }
+
+    pub fn arity(&self) -> usize {
+        match self {
+            Builtin::LEN => 1,
+            Builtin::SUBSTRING => 3,
+            Builtin::CHAR_AT => 2,
+            Builtin::SPLIT => 2,
+            Builtin::FIND => 2,
+            Builtin::PUSH => 2,
+            Builtin::POP => 1,
+            Builtin::HAS => 2,
+            Builtin::REMOVE => 2,
+            Builtin::KEYS => 1,
+        }
+    }
+
+    //args are already dereferenced
+    pub fn call(&self, args: Vec<DATA>) -> Result<DATA, RuntimeErrorKind> {
+        match self {
+            Builtin::LEN => match &args[0] {
+                DATA::List(items) => Ok(DATA::Number(items.borrow().len() as i64)),
+                DATA::Map(map) => Ok(DATA::Number(map.borrow().len() as i64)),
+                _ => Ok(DATA::Number(chars(&args[0])?.len() as i64)),
+            },
+            Builtin::SUBSTRING => {
+                let s = chars(&args[0])?;
+                //the end may be one past the last char
+                let start = index(&args[1], s.len() + 1)?;
+                let end = index(&args[2], s.len() + 1)?;
+                if start > end {
+                    return Err(RuntimeErrorKind::InvalidArgument(format!(
+                        "substring start {} is after its end {}",
+                        start, end
+                    )));
+                }
+                Ok(DATA::String(s[start..end].iter().collect()))
+            }
+            Builtin::CHAR_AT => {
+                let s = chars(&args[0])?;
+                let i = index(&args[1], s.len())?;
+                Ok(DATA::String(s[i].to_string()))
+            }
+            Builtin::SPLIT => {
+                let s = string(&args[0])?;
+                let sep = string(&args[1])?;
+                if sep.is_empty() {
+                    return Err(RuntimeErrorKind::InvalidArgument(
+                        "split separator is empty".to_string(),
+                    ));
+                }
+                Ok(DATA::list(
+                    s.split(sep).map(|x| DATA::String(x.to_string())).collect(),
+                ))
+            }
+            Builtin::FIND => {
+                let s = string(&args[0])?;
+                let needle = string(&args[1])?;
+                //index in chars, -1 when missing
+                Ok(DATA::Number(match s.find(needle) {
+                    Some(i) => s[..i].chars().count() as i64,
+                    None => -1,
+                }))
+            }
+            //returns the new length
+            Builtin::PUSH => {
+                let mut items = list(&args[0])?.borrow_mut();
+                items.push(args[1].clone());
+                Ok(DATA::Number(items.len() as i64))
+            }
+            Builtin::POP => match list(&args[0])?.borrow_mut().pop() {
+                Some(item) => Ok(item),
+                None => Err(RuntimeErrorKind::InvalidArgument(
+                    "pop from an empty list".to_string(),
+                )),
+            },
+            Builtin::HAS => {
+                let key = args[1].to_key()?;
+                Ok(DATA::Bool(map(&args[0])?.borrow().contains_key(&key)))
+            }
+            //returns the removed value
+            Builtin::REMOVE => match map(&args[0])?.borrow_mut().remove(&args[1].to_key()?) {
+                Some(value) => Ok(value),
+                None => Err(RuntimeErrorKind::MissingKey(args[1].fancy_string())),
+            },
+            Builtin::KEYS => Ok(DATA::list(
+                map(&args[0])?.borrow().keys().map(Key::to_data).collect(),
+            )),
+        }
+    }
+}
+
+fn string(data: &DATA) -> Result<&str, RuntimeErrorKind> {
+    match data {
+        DATA::String(s) => Ok(s),
+        _ => Err(RuntimeErrorKind::TypeError(format!(
+            "expected a string, found {}",
+            data.fancy_string()
+        ))),
+    }
+}
+
+fn list(data: &DATA) -> Result<&RefCell<Vec<DATA>>, RuntimeErrorKind> {
+    match data {
+        DATA::List(items) => Ok(items),
+        _ => Err(RuntimeErrorKind::TypeError(format!(
+            "expected a list, found {}",
+            data.fancy_string()
+        ))),
+    }
+}
+
+fn map(data: &DATA) -> Result<&RefCell<BTreeMap<Key, DATA>>, RuntimeErrorKind> {
+    match data {
+        DATA::Map(map) => Ok(map),
+        _ => Err(RuntimeErrorKind::TypeError(format!(
+            "expected a map, found {}",
+            data.fancy_string()
+        ))),
+    }
+}
+
+fn chars(data: &DATA) -> Result<Vec<char>, RuntimeErrorKind> {
+    Ok(string(data)?.chars().collect())
+}
+
+//an index that must be below bound
+fn index(data: &DATA, bound: usize) -> Result<usize, RuntimeErrorKind> {
+    let i = data.to_i64()?;
+    if i < 0 || i as usize >= bound {
+        return Err(RuntimeErrorKind::IndexOutOfBounds(i));
+    }
+    Ok(i as usize)
+}
@@ -1,19 +1,30 @@
+use crate::builtin::Builtin;
 use crate::interpreter::Operation;
 use crate::interpreter::DATA;
+use crate::lexer::Pos;
 use std::collections::HashMap;
+use std::fmt;
 
 #[derive(Debug, Clone)]
 pub enum Instruction {
     SET {
         name: String,
         value: Expression,
+        pos: Pos,
     },
     ASSIGN {
         name: String,
         value: Expression,
+        pos: Pos,
     },
     PRINT {
         value: Expression,
+        pos: Pos,
+    },
+    //evaluated for its side effects, the result is discarded
+    EXPRESSION {
+        value: Expression,
+        pos: Pos,
     },
     BLOCK {
         instruction: Vec<Instruction>,
@@ -21,15 +32,19 @@ pub enum Instruction {
     IF {
         condition: Expression,
         instruction: Vec<Instruction>,
+        else_instruction: Vec<Instruction>,
+        pos: Pos,
     },
     EXIT,
     FUNCTION {
         name: String,
         args: Vec<String>,
         instruction: Vec<Instruction>,
+        pos: Pos,
     },
     RETURN {
         value: Expression,
+        pos: Pos,
     },
     LOOP {
         instruction: Vec<Instruction>,
@@ -37,307 +52,495 @@ pub enum Instruction {
     WHILE {
         condition: Expression,
         instruction: Vec<Instruction>,
+        pos: Pos,
     },
     DROP {
         name: String,
+        pos: Pos,
+    },
+    //lets a function assign to a global variable
+    GLOBAL {
+        name: String,
+        pos: Pos,
+    },
+    //target[index] = value
+    SET_INDEX {
+        target: Expression,
+        index: Expression,
+        value: Expression,
+        pos: Pos,
+    },
+    FOR {
+        name: String,
+        iterable: Expression,
+        instruction: Vec<Instruction>,
+        pos: Pos,
+    },
+    STRUCT {
+        name: String,
+        fields: Vec<String>,
+        pos: Pos,
+    },
+    //target.field = value
+    SET_FIELD {
+        target: Expression,
+        field: String,
+        value: Expression,
+        pos: Pos,
+    },
+    BREAK {
+        pos: Pos,
+    },
+    CONTINUE {
+        pos: Pos,
     },
     HALT,
 }
 
-impl Instruction {
-    fn change_name(&self, add: i32, of: &mut Vec<String>) -> Instruction {
-        match self.clone() {
-            Instruction::SET { mut name, value } => {
-                of.push(name.clone());
-                name.push_str(&add.to_string());
-                Instruction::SET {
-                    name,
-                    value: value.change_name(add, of),
-                }
-            }
-            Instruction::ASSIGN { mut name, value } => {
-                if of.contains(&name) {
-                    name.push_str(&add.to_string());
-                }
-                Instruction::ASSIGN {
-                    name,
-                    value: value.change_name(add, of),
-                }
-            }
-            Instruction::PRINT { value } => Instruction::PRINT {
-                value: value.change_name(add, of),
-            },
-            Instruction::BLOCK { instruction } => {
-                let mut new_instruction = Vec::new();
-                for i in instruction {
-                    new_instruction.push(i.change_name(add, of));
-                }
-                Instruction::BLOCK {
-                    instruction: new_instruction,
-                }
-            }
-            Instruction::IF {
-                condition,
-                instruction,
-            } => Instruction::IF {
-                condition: condition.change_name(add, of),
-                instruction: instruction.iter().map(|x| x.change_name(add, of)).collect(),
-            },
-            Instruction::DROP { mut name } => {
-                if of.contains(&name) {
-                    name.push_str(&add.to_string());
-                    Instruction::DROP { name }
-                } else {
-                    Instruction::DROP { name }
-                }
-            }
-            Instruction::FUNCTION {
-                mut name,
-                args,
-                instruction,
-            } => {
-                of.push(name.clone());
-                name.push_str(&add.to_string());
-                let mut new_instruction = Vec::new();
-                for i in instruction {
-                    new_instruction.push(i.change_name(add, of));
-                }
-                Instruction::FUNCTION {
-                    name,
-                    args,
-                    instruction: new_instruction,
-                }
-            }
-            Instruction::RETURN { value } => Instruction::RETURN {
-                value: value.change_name(add, of),
-            },
-            Instruction::LOOP { instruction } => {
-                let mut new_instruction = Vec::new();
-                for i in instruction {
-                    new_instruction.push(i.change_name(add, of));
-                }
-                Instruction::LOOP {
-                    instruction: new_instruction,
-                }
-            }
-            Instruction::WHILE {
-                condition,
-                instruction,
-            } => Instruction::WHILE {
-                condition: condition.change_name(add, of),
-                instruction: instruction.iter().map(|x| x.change_name(add, of)).collect(),
-            },
-            _ => self.clone(),
-        }
-    }
-}
-
 #[derive(Debug, Clone)]
 pub enum Expression {
-    Number(i32),
-    CALL(String, Vec<Expression>),
+    Number(i64),
+    Float(f64),
+    //the position is the call site
+    CALL(String, Vec<Expression>, Pos),
+    //calls a function value
+    CALL_VALUE(Box<Expression>, Vec<Expression>, Pos),
+    Lambda(Vec<String>, Vec<Instruction>),
     Bool(bool),
     String(String),
-    Variable(String),
+    //string interpolation, the parts are concatenated
+    Template(Vec<Expression>),
+    Variable(String, Pos),
+    List(Vec<Expression>),
+    Map(Vec<(Expression, Expression)>),
+    Index(Box<Expression>, Box<Expression>),
+    //struct name and field values, in source order
+    //the name's position, and each field's own
+    Record(String, Vec<(String, Expression, Pos)>, Pos),
+    Field(Box<Expression>, String),
     Add(Box<Expression>, Box<Expression>),
     Sub(Box<Expression>, Box<Expression>),
     Mul(Box<Expression>, Box<Expression>),
     Div(Box<Expression>, Box<Expression>),
+    Mod(Box<Expression>, Box<Expression>),
+    Neg(Box<Expression>),
     EQ(Box<Expression>, Box<Expression>),
     NOT(Box<Expression>),
+    NOT_EQ(Box<Expression>, Box<Expression>),
     GREATER(Box<Expression>, Box<Expression>),
+    GREATER_EQ(Box<Expression>, Box<Expression>),
+    LESS(Box<Expression>, Box<Expression>),
+    LESS_EQ(Box<Expression>, Box<Expression>),
+    AND(Box<Expression>, Box<Expression>),
+    OR(Box<Expression>, Box<Expression>),
+}
+
+//the operand that reads the value stored at a destination address
+fn pointer(addr: &DATA) -> DATA {
+    match addr {
+        DATA::Number(n) => DATA::POINTER(*n),
+        _ => addr.clone(),
+    }
 }
 
 impl Expression {
-    fn change_name(&self, add: i32, of: &mut Vec<String>) -> Self {
-        match self.clone() {
-            Expression::CALL(mut name, data) => {
-                if of.contains(&name) {
-                    name.push_str(&add.to_string());
-                }
-                let mut new_data = Vec::new();
-                for i in data {
-                    new_data.push(i.change_name(add, of));
-                }
-                Expression::CALL(name, new_data)
-            }
-            Expression::Variable(mut name) => {
-                if of.contains(&name) {
-                    name.push_str(&add.to_string());
-                    Expression::Variable(name)
-                } else {
-                    Expression::Variable(name)
-                }
-            }
-            Expression::Add(a, b) => Expression::Add(
-                Box::new(a.change_name(add, of)),
-                Box::new(b.change_name(add, of)),
-            ),
-            Expression::Sub(a, b) => Expression::Sub(
-                Box::new(a.change_name(add, of)),
-                Box::new(b.change_name(add, of)),
-            ),
-            Expression::Mul(a, b) => Expression::Mul(
-                Box::new(a.change_name(add, of)),
-                Box::new(b.change_name(add, of)),
-            ),
-            Expression::Div(a, b) => Expression::Div(
-                Box::new(a.change_name(add, of)),
-                Box::new(b.change_name(add, of)),
-            ),
-            Expression::EQ(a, b) => Expression::EQ(
-                Box::new(a.change_name(add, of)),
-                Box::new(b.change_name(add, of)),
-            ),
-            Expression::NOT(a) => Expression::NOT(Box::new(a.change_name(add, of))),
-            Expression::GREATER(a, b) => Expression::GREATER(
-                Box::new(a.change_name(add, of)),
-                Box::new(b.change_name(add, of)),
-            ),
-            _ => self.clone(),
+    fn binary(
+        a: &Expression,
+        b: &Expression,
+        addr: DATA,
+        compiler: &mut Compiler,
+        op: fn(DATA, DATA, DATA) -> Operation,
+    ) -> Result<Vec<Operation>, CompileError> {
+        let addr_a = compiler.new_addr();
+        let addr_b = compiler.new_addr();
+        let mut ops = a.to_addr(addr_a.clone(), compiler)?;
+        ops.extend(b.to_addr(addr_b.clone(), compiler)?);
+        ops.push(op(pointer(&addr_a), pointer(&addr_b), addr));
+        Ok(ops)
+    }
+
+    //stores true or false depending on whether the value is truthy
+    fn truthy(&self, addr: DATA, compiler: &mut Compiler) -> Result<Vec<Operation>, CompileError> {
+        let addr_a = compiler.new_addr();
+        let mut ops = Expression::NOT(Box::new(self.clone())).to_addr(addr_a.clone(), compiler)?;
+        ops.push(Operation::NOT {
+            name: pointer(&addr_a),
+            ret: addr,
+        });
+        Ok(ops)
+    }
+
+    //`and` skips b when a is false, `or` skips b when a is true
+    fn short_circuit(
+        a: &Expression,
+        b: &Expression,
+        addr: DATA,
+        compiler: &mut Compiler,
+        and: bool,
+    ) -> Result<Vec<Operation>, CompileError> {
+        let result = compiler.new_addr();
+        let end = compiler.new_label();
+        let mut ops = a.truthy(result.clone(), compiler)?;
+        let condition = if and {
+            let not_a = compiler.new_addr();
+            ops.push(Operation::NOT {
+                name: pointer(&result),
+                ret: not_a.clone(),
+            });
+            not_a
+        } else {
+            result.clone()
+        };
+        ops.push(Operation::JUMP_IF {
+            name: DATA::Number(end),
+            condition: pointer(&condition),
+        });
+        ops.extend(b.truthy(result.clone(), compiler)?);
+        ops.push(Operation::POINT {
+            name: DATA::Number(end),
+        });
+        ops.push(Operation::SET {
+            name: addr,
+            value: pointer(&result),
+        });
+        Ok(ops)
+    }
+
+    //evaluate every argument before pushing any, so calls nested
+    //in the arguments do not pick up this call's arguments
+    fn call_args(
+        args: &[Expression],
+        compiler: &mut Compiler,
+    ) -> Result<Vec<Operation>, CompileError> {
+        let mut ops = vec![];
+        let mut arg_addrs = vec![];
+        for arg in args {
+            let arg_addr = compiler.new_addr();
+            ops.extend(arg.to_addr(arg_addr.clone(), compiler)?);
+            arg_addrs.push(arg_addr);
+        }
+        for arg_addr in arg_addrs {
+            ops.push(Operation::ARG {
+                value: pointer(&arg_addr),
+            });
+        }
+        Ok(ops)
+    }
+
+    fn call_builtin(
+        func: Builtin,
+        name: &str,
+        args: &[Expression],
+        pos: Pos,
+        addr: DATA,
+        compiler: &mut Compiler,
+    ) -> Result<Vec<Operation>, CompileError> {
+        if args.len() != func.arity() {
+            return Err(CompileError::ArityMismatch {
+                name: name.to_string(),
+                expected: func.arity(),
+                found: args.len(),
+                pos,
+                defined_at: None,
+            });
+        }
+        let mut ops = vec![];
+        let mut arg_addrs = vec![];
+        for arg in args {
+            let arg_addr = compiler.new_addr();
+            ops.extend(arg.to_addr(arg_addr.clone(), compiler)?);
+            arg_addrs.push(pointer(&arg_addr));
         }
+        ops.push(Operation::BUILTIN {
+            func,
+            args: arg_addrs,
+            ret: addr,
+        });
+        Ok(ops)
     }
 
-    pub fn to_addr(&self, addr: i32, compiler: &mut Compiler) -> Vec<Operation> {
+    pub fn to_addr(
+        &self,
+        addr: DATA,
+        compiler: &mut Compiler,
+    ) -> Result<Vec<Operation>, CompileError> {
         match self {
             Expression::GREATER(a, b) => {
-                let addr_a = compiler.new_addr();
-                let addr_b = compiler.new_addr();
-                let mut ops_a = a.to_addr(addr_a, compiler);
-                let ops_b = b.to_addr(addr_b, compiler);
-                let ops = vec![Operation::GREATER {
-                    name: DATA::POINTER(addr_a),
-                    value: DATA::POINTER(addr_b),
-                    ret: DATA::Number(addr),
-                }];
-                ops_a.extend(ops_b);
-                ops_a.extend(ops);
-                ops_a
+                Expression::binary(a, b, addr, compiler, |name, value, ret| {
+                    Operation::GREATER { name, value, ret }
+                })
             }
-            Expression::EQ(a, b) => {
-                let addr_a = compiler.new_addr();
-                let addr_b = compiler.new_addr();
-                let mut ops_a = a.to_addr(addr_a, compiler);
-                let ops_b = b.to_addr(addr_b, compiler);
-                let ops = vec![Operation::EQ {
-                    name: DATA::POINTER(addr_a),
-                    value: DATA::POINTER(addr_b),
-                    ret: DATA::Number(addr),
-                }];
-                ops_a.extend(ops_b);
-                ops_a.extend(ops);
-                ops_a
+            Expression::GREATER_EQ(a, b) => {
+                Expression::binary(a, b, addr, compiler, |name, value, ret| {
+                    Operation::GREATER_EQ { name, value, ret }
+                })
+            }
+            //a < b is b > a, with a still evaluated first
+            Expression::LESS(a, b) => {
+                Expression::binary(a, b, addr, compiler, |name, value, ret| {
+                    Operation::GREATER {
+                        name: value,
+                        value: name,
+                        ret,
+                    }
+                })
+            }
+            Expression::LESS_EQ(a, b) => {
+                Expression::binary(a, b, addr, compiler, |name, value, ret| {
+                    Operation::GREATER_EQ {
+                        name: value,
+                        value: name,
+                        ret,
+                    }
+                })
+            }
+            Expression::NOT_EQ(a, b) => {
+                Expression::NOT(Box::new(Expression::EQ(a.clone(), b.clone())))
+                    .to_addr(addr, compiler)
             }
+            Expression::AND(a, b) => Expression::short_circuit(a, b, addr, compiler, true),
+            Expression::OR(a, b) => Expression::short_circuit(a, b, addr, compiler, false),
+            Expression::EQ(a, b) => Expression::binary(a, b, addr, compiler, |name, value, ret| {
+                Operation::EQ { name, value, ret }
+            }),
             Expression::NOT(a) => {
                 let addr_a = compiler.new_addr();
-                let mut ops_a = a.to_addr(addr_a, compiler);
-                let ops = vec![Operation::NOT {
-                    name: DATA::POINTER(addr_a),
-                    ret: DATA::Number(addr),
-                }];
-                ops_a.extend(ops);
-                ops_a
+                let mut ops_a = a.to_addr(addr_a.clone(), compiler)?;
+                ops_a.push(Operation::NOT {
+                    name: pointer(&addr_a),
+                    ret: addr,
+                });
+                Ok(ops_a)
             }
-            Expression::Number(n) => {
-                vec![Operation::SET {
-                    name: DATA::Number(addr),
-                    value: DATA::Number(*n),
-                }]
+            Expression::Number(n) => Ok(vec![Operation::SET {
+                name: addr,
+                value: DATA::Number(*n),
+            }]),
+            Expression::Float(f) => Ok(vec![Operation::SET {
+                name: addr,
+                value: DATA::Float(*f),
+            }]),
+            Expression::Bool(b) => Ok(vec![Operation::SET {
+                name: addr,
+                value: DATA::Bool(*b),
+            }]),
+            Expression::String(s) => Ok(vec![Operation::SET {
+                name: addr,
+                value: DATA::String(s.clone()),
+            }]),
+            //start from "" so + concatenates even when the parts are numbers
+            Expression::Template(parts) => parts
+                .iter()
+                .fold(Expression::String(String::new()), |a, b| {
+                    Expression::Add(Box::new(a), Box::new(b.clone()))
+                })
+                .to_addr(addr, compiler),
+            Expression::List(items) => {
+                let mut ops = vec![];
+                let mut item_addrs = vec![];
+                for item in items {
+                    let item_addr = compiler.new_addr();
+                    ops.extend(item.to_addr(item_addr.clone(), compiler)?);
+                    item_addrs.push(pointer(&item_addr));
+                }
+                ops.push(Operation::LIST {
+                    items: item_addrs,
+                    ret: addr,
+                });
+                Ok(ops)
             }
-            Expression::Bool(b) => {
-                vec![Operation::SET {
-                    name: DATA::Number(addr),
-                    value: DATA::Bool(*b),
-                }]
+            Expression::Map(entries) => {
+                let mut ops = vec![];
+                let mut entry_addrs = vec![];
+                for (key, value) in entries {
+                    let key_addr = compiler.new_addr();
+                    let value_addr = compiler.new_addr();
+                    ops.extend(key.to_addr(key_addr.clone(), compiler)?);
+                    ops.extend(value.to_addr(value_addr.clone(), compiler)?);
+                    entry_addrs.push((pointer(&key_addr), pointer(&value_addr)));
+                }
+                ops.push(Operation::MAP {
+                    entries: entry_addrs,
+                    ret: addr,
+                });
+                Ok(ops)
             }
-            Expression::String(s) => {
-                vec![Operation::SET {
-                    name: DATA::Number(addr),
-                    value: DATA::String(s.clone()),
-                }]
-            }
-            Expression::Variable(v) => {
-                let addr2 = compiler.vars.get(v).unwrap_or_else(|| {
-                    println!("var not defined: {}", v);
-                    println!("{:?}", compiler.vars);
-                    std::process::exit(1);
+            Expression::Record(name, values, pos) => {
+                let fields = match compiler.structs.get(name) {
+                    Some(def) => def.fields.clone(),
+                    None => {
+                        return Err(CompileError::UnknownStruct {
+                            name: name.clone(),
+                            pos: *pos,
+                        })
+                    }
+                };
+                let mut ops = vec![];
+                let mut value_addrs: Vec<(String, DATA)> = vec![];
+                for (field, value, field_pos) in values {
+                    if !fields.contains(field) {
+                        return Err(CompileError::UnknownField {
+                            name: name.clone(),
+                            field: field.clone(),
+                            pos: *field_pos,
+                        });
+                    }
+                    if value_addrs.iter().any(|(f, _)| f == field) {
+                        return Err(CompileError::DuplicateField {
+                            name: name.clone(),
+                            field: field.clone(),
+                            pos: *field_pos,
+                        });
+                    }
+                    let value_addr = compiler.new_addr();
+                    ops.extend(value.to_addr(value_addr.clone(), compiler)?);
+                    value_addrs.push((field.clone(), pointer(&value_addr)));
+                }
+                //the record stores its fields in declaration order
+                let mut record_fields = vec![];
+                for field in fields {
+                    match value_addrs.iter().find(|(f, _)| *f == field) {
+                        Some((_, value_addr)) => record_fields.push((field, value_addr.clone())),
+                        None => {
+                            return Err(CompileError::MissingField {
+                                name: name.clone(),
+                                field,
+                                pos: *pos,
+                            })
+                        }
+                    }
+                }
+                ops.push(Operation::RECORD {
+                    name: name.clone(),
+                    fields: record_fields,
+                    ret: addr,
                 });
-                vec![Operation::SET {
-                    name: DATA::Number(addr),
-                    value: DATA::POINTER(addr2.clone()),
-                }]
+                Ok(ops)
             }
-            Expression::Add(a, b) => {
+            Expression::Field(a, field) => {
                 let addr_a = compiler.new_addr();
-                let addr_b = compiler.new_addr();
-                let mut ops_a = a.to_addr(addr_a, compiler);
-                let ops_b = b.to_addr(addr_b, compiler);
-                let ops = vec![Operation::ADD {
-                    name: DATA::POINTER(addr_a),
-                    value: DATA::POINTER(addr_b),
-                    ret: DATA::Number(addr),
-                }];
-                ops_a.extend(ops_b);
-                ops_a.extend(ops);
-                ops_a
+                let mut ops = a.to_addr(addr_a.clone(), compiler)?;
+                ops.push(Operation::GET_FIELD {
+                    name: pointer(&addr_a),
+                    field: field.clone(),
+                    ret: addr,
+                });
+                Ok(ops)
+            }
+            Expression::Index(a, b) => {
+                Expression::binary(a, b, addr, compiler, |name, index, ret| Operation::INDEX {
+                    name,
+                    index,
+                    ret,
+                })
+            }
+            Expression::Variable(v, pos) => match compiler.lookup(v) {
+                Some(addr2) => Ok(vec![Operation::SET {
+                    name: addr,
+                    value: pointer(&addr2),
+                }]),
+                //a named function used as a value
+                None if let Some(func) = compiler.function(v) => Ok(vec![Operation::CLOSURE {
+                    name: DATA::Number(func.addr),
+                    arity: func.args.len(),
+                    captures: vec![],
+                    self_slot: None,
+                    ret: addr,
+                }]),
+                None => Err(CompileError::UndefinedVariable {
+                    name: v.clone(),
+                    pos: *pos,
+                }),
+            },
+            Expression::Lambda(args, instruction) => {
+                compiler.compile_lambda(args.clone(), instruction.clone(), addr, None)
+            }
+            Expression::CALL_VALUE(callee, args, _) => {
+                let callee_addr = compiler.new_addr();
+                let mut ops = callee.to_addr(callee_addr.clone(), compiler)?;
+                ops.extend(Expression::call_args(args, compiler)?);
+                ops.push(Operation::CALL_VALUE {
+                    name: pointer(&callee_addr),
+                });
+                ops.push(Operation::RESULT { name: addr });
+                Ok(ops)
+            }
+            Expression::Add(a, b) => {
+                Expression::binary(a, b, addr, compiler, |name, value, ret| Operation::ADD {
+                    name,
+                    value,
+                    ret,
+                })
             }
             Expression::Sub(a, b) => {
-                let addr_a = compiler.new_addr();
-                let addr_b = compiler.new_addr();
-                let mut ops_a = a.to_addr(addr_a, compiler);
-                let ops_b = b.to_addr(addr_b, compiler);
-                let ops = vec![Operation::SUB {
-                    name: DATA::POINTER(addr_a),
-                    value: DATA::POINTER(addr_b),
-                    ret: DATA::Number(addr),
-                }];
-                ops_a.extend(ops_b);
-                ops_a.extend(ops);
-                ops_a
+                Expression::binary(a, b, addr, compiler, |name, value, ret| Operation::SUB {
+                    name,
+                    value,
+                    ret,
+                })
             }
             Expression::Mul(a, b) => {
-                let addr_a = compiler.new_addr();
-                let addr_b = compiler.new_addr();
-                let mut ops_a = a.to_addr(addr_a, compiler);
-                let ops_b = b.to_addr(addr_b, compiler);
-                let ops = vec![Operation::MUL {
-                    name: DATA::POINTER(addr_a),
-                    value: DATA::POINTER(addr_b),
-                    ret: DATA::Number(addr),
-                }];
-                ops_a.extend(ops_b);
-                ops_a.extend(ops);
-                ops_a
+                Expression::binary(a, b, addr, compiler, |name, value, ret| Operation::MUL {
+                    name,
+                    value,
+                    ret,
+                })
             }
             Expression::Div(a, b) => {
-                let addr_a = compiler.new_addr();
-                let addr_b = compiler.new_addr();
-                let mut ops_a = a.to_addr(addr_a, compiler);
-                let ops_b = b.to_addr(addr_b, compiler);
-                let ops = vec![Operation::DIV {
-                    name: DATA::POINTER(addr_a),
-                    value: DATA::POINTER(addr_b),
-                    ret: DATA::Number(addr),
-                }];
-                ops_a.extend(ops_b);
-                ops_a.extend(ops);
-                ops_a
-            }
-            Expression::CALL(name, args) => {
-                let func = compiler.functions.get(name).unwrap().clone();
-                let mut ops = vec![Operation::SET {
-                    name: DATA::Number(func.return_addr),
-                    value: DATA::Number(addr),
-                }];
-                for (i, arg) in args.iter().zip(func.args.iter()) {
-                    let arg_addr = compiler.vars[arg];
-                    let mut arg_ops = i.to_addr(arg_addr, compiler);
-                    ops.append(&mut arg_ops);
+                Expression::binary(a, b, addr, compiler, |name, value, ret| Operation::DIV {
+                    name,
+                    value,
+                    ret,
+                })
+            }
+            Expression::Mod(a, b) => {
+                Expression::binary(a, b, addr, compiler, |name, value, ret| Operation::MOD {
+                    name,
+                    value,
+                    ret,
+                })
+            }
+            //-a is 0 - a
+            Expression::Neg(a) => {
+                Expression::Sub(Box::new(Expression::Number(0)), a.clone()).to_addr(addr, compiler)
+            }
+            //a variable holding a function shadows functions of the same name
+            Expression::CALL(name, args, _) if let Some(callee) = compiler.lookup(name) => {
+                let mut ops = Expression::call_args(args, compiler)?;
+                ops.push(Operation::CALL_VALUE {
+                    name: pointer(&callee),
+                });
+                ops.push(Operation::RESULT { name: addr });
+                Ok(ops)
+            }
+            Expression::CALL(name, args, pos) => {
+                let func = match compiler.function(name) {
+                    Some(func) => func.clone(),
+                    None if let Some(func) = Builtin::from_name(name) => {
+                        return Expression::call_builtin(func, name, args, *pos, addr, compiler)
+                    }
+                    None => {
+                        return Err(CompileError::UnknownFunction {
+                            name: name.clone(),
+                            pos: *pos,
+                        })
+                    }
+                };
+                if args.len() != func.args.len() {
+                    return Err(CompileError::ArityMismatch {
+                        name: name.clone(),
+                        expected: func.args.len(),
+                        found: args.len(),
+                        pos: *pos,
+                        defined_at: Some(func.pos),
+                    });
                 }
+                let mut ops = Expression::call_args(args, compiler)?;
                 ops.push(Operation::CALL {
                     name: DATA::Number(func.addr),
                 });
-                ops
+                ops.push(Operation::RESULT { name: addr });
+                Ok(ops)
             }
         }
     }
@@ -347,32 +550,261 @@ impl Expression {
 pub struct FUNCTION {
     pub name: String,
     pub args: Vec<String>,
-    pub return_addr: i32,
-    pub addr: i32,
+    pub addr: i64,
+    //false while the function is only hoisted and its body is not compiled yet
+    pub defined: bool,
+    pub pos: Pos,
+}
+
+#[derive(Debug, Clone)]
+pub struct STRUCT {
+    pub fields: Vec<String>,
+    //false while the struct is only hoisted and its statement is not compiled yet
+    pub defined: bool,
+    pub pos: Pos,
+}
+
+//frame slots of the function currently being compiled
+#[derive(Debug, Clone, Default)]
+pub struct Locals {
+    pub size: i64,
+    //globals the function declared with `global`
+    pub globals: Vec<String>,
+    //slots holding values copied in by a lambda
+    pub captured: Vec<i64>,
+}
+
+//the code around a function body, saved while the body is compiled
+#[derive(Debug, Clone, Default)]
+pub struct Enclosing {
+    pub locals: Option<Locals>,
+    pub scopes: Vec<Scope>,
+    pub loops: Vec<(i64, i64, usize)>,
+    //slots of the lambda inside and the outer addresses copied into them
+    pub captures: Vec<(i64, DATA)>,
+}
+
+//the variables declared in one block, dropped when the block ends
+#[derive(Debug, Clone, Default)]
+pub struct Scope {
+    pub vars: HashMap<String, DATA>,
+    pub slots: Vec<DATA>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CompileError {
+    UndefinedVariable {
+        name: String,
+        pos: Pos,
+    },
+    UnknownFunction {
+        name: String,
+        pos: Pos,
+    },
+    ArityMismatch {
+        name: String,
+        expected: usize,
+        found: usize,
+        pos: Pos,
+        //none for builtins
+        defined_at: Option<Pos>,
+    },
+    DuplicateFunction {
+        name: String,
+        pos: Pos,
+        defined_at: Pos,
+    },
+    DuplicateStruct {
+        name: String,
+        pos: Pos,
+        defined_at: Pos,
+    },
+    MisplacedReturn {
+        pos: Pos,
+    },
+    MisplacedBreak {
+        pos: Pos,
+    },
+    MisplacedContinue {
+        pos: Pos,
+    },
+    DropUndeclared {
+        name: String,
+        pos: Pos,
+    },
+    MisplacedGlobal {
+        pos: Pos,
+    },
+    AssignGlobal {
+        name: String,
+        pos: Pos,
+    },
+    AssignCaptured {
+        name: String,
+        pos: Pos,
+    },
+    DropGlobal {
+        name: String,
+        pos: Pos,
+    },
+    UnknownStruct {
+        name: String,
+        pos: Pos,
+    },
+    UnknownField {
+        name: String,
+        field: String,
+        pos: Pos,
+    },
+    MissingField {
+        name: String,
+        field: String,
+        pos: Pos,
+    },
+    DuplicateField {
+        name: String,
+        field: String,
+        pos: Pos,
+    },
+}
+
+impl CompileError {
+    pub fn pos(&self) -> Pos {
+        match self {
+            CompileError::UndefinedVariable { pos, .. } => *pos,
+            CompileError::UnknownFunction { pos, .. } => *pos,
+            CompileError::ArityMismatch { pos, .. } => *pos,
+            CompileError::DuplicateFunction { pos, .. } => *pos,
+            CompileError::DuplicateStruct { pos, .. } => *pos,
+            CompileError::MisplacedReturn { pos } => *pos,
+            CompileError::MisplacedBreak { pos } => *pos,
+            CompileError::MisplacedContinue { pos } => *pos,
+            CompileError::DropUndeclared { pos, .. } => *pos,
+            CompileError::MisplacedGlobal { pos } => *pos,
+            CompileError::AssignGlobal { pos, .. } => *pos,
+            CompileError::AssignCaptured { pos, .. } => *pos,
+            CompileError::DropGlobal { pos, .. } => *pos,
+            CompileError::UnknownStruct { pos, .. } => *pos,
+            CompileError::UnknownField { pos, .. } => *pos,
+            CompileError::MissingField { pos, .. } => *pos,
+            CompileError::DuplicateField { pos, .. } => *pos,
+        }
+    }
 }
 
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "compile error at {}: ", self.pos())?;
+        match self {
+            CompileError::UndefinedVariable { name, .. } => {
+                write!(f, "variable `{}` is not defined", name)
+            }
+            CompileError::UnknownFunction { name, .. } => {
+                write!(f, "function `{}` is not defined", name)
+            }
+            CompileError::ArityMismatch {
+                name,
+                expected,
+                found,
+                defined_at,
+                ..
+            } => {
+                write!(
+                    f,
+                    "function `{}` takes {} argument(s) but {} were given",
+                    name, expected, found
+                )?;
+                match defined_at {
+                    Some(defined_at) => write!(f, " (defined at {})", defined_at),
+                    None => Ok(()),
+                }
+            }
+            CompileError::DuplicateFunction {
+                name, defined_at, ..
+            } => write!(
+                f,
+                "function `{}` is already defined at {}",
+                name, defined_at
+            ),
+            CompileError::DuplicateStruct {
+                name, defined_at, ..
+            } => write!(f, "struct `{}` is already defined at {}", name, defined_at),
+            CompileError::MisplacedReturn { .. } => {
+                write!(f, "`return` is only allowed inside a function")
+            }
+            CompileError::MisplacedBreak { .. } => {
+                write!(f, "`break` is only allowed inside a loop")
+            }
+            CompileError::MisplacedContinue { .. } => {
+                write!(f, "`continue` is only allowed inside a loop")
+            }
+            CompileError::DropUndeclared { name, .. } => {
+                write!(f, "cannot drop `{}`, it is not defined", name)
+            }
+            CompileError::MisplacedGlobal { .. } => {
+                write!(f, "`global` is only allowed inside a function")
+            }
+            CompileError::AssignGlobal { name, .. } => write!(
+                f,
+                "cannot change global `{}` without `global {}` in the function",
+                name, name
+            ),
+            CompileError::AssignCaptured { name, .. } => write!(
+                f,
+                "cannot assign to `{}`, the function only holds a copy of it",
+                name
+            ),
+            CompileError::DropGlobal { name, .. } => {
+                write!(f, "cannot drop global `{}` inside a function", name)
+            }
+            CompileError::UnknownStruct { name, .. } => {
+                write!(f, "struct `{}` is not defined", name)
+            }
+            CompileError::UnknownField { name, field, .. } => {
+                write!(f, "struct `{}` has no field `{}`", name, field)
+            }
+            CompileError::MissingField { name, field, .. } => {
+                write!(f, "missing field `{}` in `{}`", field, name)
+            }
+            CompileError::DuplicateField { name, field, .. } => {
+                write!(f, "field `{}` of `{}` is given more than once", field, name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CompileError {}
+
 #[derive(Debug, Clone)]
 pub struct Compiler {
     pub instructions: Vec<Instruction>,
     pub program: Vec<Operation>,
     pub fuctions_programms: Vec<Operation>,
     pub functions: HashMap<String, FUNCTION>,
-    pub vars: HashMap<String, i32>,
-    pub caddr: i32,
+    //defs private to each function being compiled, innermost last
+    pub nested: Vec<HashMap<String, FUNCTION>>,
+    //every declared struct
+    pub structs: HashMap<String, STRUCT>,
+    pub vars: HashMap<String, i64>,
+    //top level lets of the unit not reached yet, function bodies may already use them
+    pub hoisted: HashMap<String, i64>,
+    pub locals: Option<Locals>,
+    //block scopes of the current function or the top level, innermost last
+    pub scopes: Vec<Scope>,
+    //start and end labels and scope depth of the enclosing loops, innermost last
+    pub loops: Vec<(i64, i64, usize)>,
+    //the code around each lambda being compiled, outermost first
+    pub enclosing: Vec<Enclosing>,
+    pub caddr: i64,
+    pub pos: Pos,
 }
 
 impl Compiler {
-    pub fn compile(p: Vec<Instruction>) -> Vec<Operation> {
-        let mut compiler = Compiler {
-            instructions: p,
-            program: Vec::new(),
-            vars: HashMap::new(),
-            caddr: 0,
-            functions: HashMap::new(),
-            fuctions_programms: Vec::new(),
-        };
-        compiler.compile_instructions();
-        compiler.program
+    pub fn compile(p: Vec<Instruction>) -> Result<Vec<Operation>, CompileError> {
+        let mut compiler = Compiler::continues();
+        compiler.instructions = p;
+        compiler.compile_instructions()?;
+        Ok(compiler.program)
     }
 
     pub fn continues() -> Compiler {
@@ -380,207 +812,783 @@ impl Compiler {
             instructions: Vec::new(),
             program: Vec::new(),
             vars: HashMap::new(),
+            hoisted: HashMap::new(),
+            locals: None,
+            scopes: Vec::new(),
+            loops: Vec::new(),
+            enclosing: Vec::new(),
             caddr: 0,
             functions: HashMap::new(),
+            nested: Vec::new(),
+            structs: HashMap::new(),
             fuctions_programms: Vec::new(),
+            pos: Pos::default(),
         }
     }
 
-    pub fn continues_compile(&mut self, p: Vec<Instruction>) -> Vec<Operation> {
+    //on error the compiler is left as it was before the call
+    pub fn continues_compile(
+        &mut self,
+        p: Vec<Instruction>,
+    ) -> Result<Vec<Operation>, CompileError> {
+        let backup = self.clone();
         self.instructions = p;
         self.program = Vec::new();
-        self.compile_instructions();
-        self.program.clone()
+        if let Err(e) = self.compile_instructions() {
+            *self = backup;
+            return Err(e);
+        }
+        Ok(self.program.clone())
     }
 
-    fn new_addr(&mut self) -> i32 {
+    fn new_label(&mut self) -> i64 {
         let addr = self.caddr;
         self.caddr += 1;
         addr
     }
 
-    fn new_var(&mut self, name: String) -> i32 {
-        let addr = self.new_addr();
-        self.vars.insert(name, addr);
-        addr
+    //a global address at the top level, a slot of the current frame inside a function
+    fn new_addr(&mut self) -> DATA {
+        match &mut self.locals {
+            Some(locals) => {
+                let slot = locals.size;
+                locals.size += 1;
+                DATA::LOCAL(slot)
+            }
+            None => DATA::Number(self.new_label()),
+        }
     }
 
-    fn compile_instruction(&mut self, inst: Instruction) -> Vec<Operation> {
-        match inst {
-            Instruction::HALT => {
-                vec![Operation::HALT]
+    //variables outside of any block are globals and live until dropped by hand
+    fn bind_var(&mut self, name: String, addr: DATA) {
+        match (self.scopes.last_mut(), &addr) {
+            (Some(scope), _) => {
+                scope.vars.insert(name, addr.clone());
+                scope.slots.push(addr);
+            }
+            (None, DATA::Number(n)) => {
+                self.vars.insert(name, *n);
             }
+            _ => unreachable!(),
+        }
+    }
+
+    fn drops(scopes: &[Scope]) -> Vec<Operation> {
+        scopes
+            .iter()
+            .rev()
+            .flat_map(|scope| scope.slots.iter().rev())
+            .map(|addr| Operation::DROP { name: addr.clone() })
+            .collect()
+    }
+
+    fn compile_scoped(
+        &mut self,
+        instruction: Vec<Instruction>,
+    ) -> Result<Vec<Operation>, CompileError> {
+        self.scopes.push(Scope::default());
+        let mut ops = vec![];
+        for i in instruction {
+            let mut iops = self.compile_instruction(i)?;
+            ops.append(&mut iops);
+        }
+        let scope = self.scopes.pop().unwrap();
+        ops.extend(Compiler::drops(&[scope]));
+        Ok(ops)
+    }
+
+    fn declare_function(
+        &mut self,
+        name: String,
+        args: Vec<String>,
+        pos: Pos,
+        defined: bool,
+    ) -> FUNCTION {
+        let func = FUNCTION {
+            name: name.clone(),
+            args,
+            addr: self.new_label(),
+            defined,
+            pos,
+        };
+        self.function_table().insert(name, func.clone());
+        func
+    }
+
+    //the defs of the innermost function, or the global ones at the top level
+    fn function_table(&mut self) -> &mut HashMap<String, FUNCTION> {
+        match self.nested.last_mut() {
+            Some(table) => table,
+            None => &mut self.functions,
+        }
+    }
+
+    //a def of an enclosing function shadows a global one
+    fn function(&self, name: &str) -> Option<&FUNCTION> {
+        self.nested
+            .iter()
+            .rev()
+            .find_map(|table| table.get(name))
+            .or_else(|| self.functions.get(name))
+    }
+
+    fn lookup(&mut self, name: &str) -> Option<DATA> {
+        for scope in self.scopes.iter().rev() {
+            if let Some(addr) = scope.vars.get(name) {
+                return Some(addr.clone());
+            }
+        }
+        if self.declared_global(name) {
+            return self.global(name).map(DATA::Number);
+        }
+        if let Some(addr) = self.capture(name, self.enclosing.len()) {
+            return Some(addr);
+        }
+        self.global(name).map(DATA::Number)
+    }
+
+    //code at the top level only sees globals already declared,
+    //functions also see the ones declared later in the unit
+    fn global(&self, name: &str) -> Option<i64> {
+        match self.vars.get(name) {
+            Some(addr) => Some(*addr),
+            None if self.locals.is_some() => self.hoisted.get(name).copied(),
+            None => None,
+        }
+    }
+
+    fn var(&mut self, name: &str) -> Result<DATA, CompileError> {
+        match self.lookup(name) {
+            Some(addr) => Ok(addr),
+            None => Err(CompileError::UndefinedVariable {
+                name: name.to_string(),
+                pos: self.pos,
+            }),
+        }
+    }
+
+    fn declared_global(&self, name: &str) -> bool {
+        match &self.locals {
+            Some(locals) => locals.globals.iter().any(|global| global == name),
+            None => false,
+        }
+    }
+
+    //functions may read any global but only change the ones they declared,
+    //and a lambda may not assign to the copies it captured
+    fn assignable(&mut self, name: &str) -> Result<DATA, CompileError> {
+        let addr = self.var(name)?;
+        match (&self.locals, &addr) {
+            (Some(_), DATA::Number(_)) if !self.declared_global(name) => {
+                Err(CompileError::AssignGlobal {
+                    name: name.to_string(),
+                    pos: self.pos,
+                })
+            }
+            (Some(locals), DATA::LOCAL(slot)) if locals.captured.contains(slot) => {
+                Err(CompileError::AssignCaptured {
+                    name: name.to_string(),
+                    pos: self.pos,
+                })
+            }
+            _ => Ok(addr),
+        }
+    }
+
+    //looks for name around the lambda at the given nesting level and, if found,
+    //copies it into a new slot of that lambda; captures are by value.
+    //globals are never captured, every function reads them when it runs
+    fn capture(&mut self, name: &str, level: usize) -> Option<DATA> {
+        if level == 0 {
+            return None;
+        }
+        let found = self.enclosing[level - 1]
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.vars.get(name).cloned());
+        let outer_addr = match found {
+            Some(addr) => addr,
+            None => self.capture(name, level - 1)?,
+        };
+        let (locals, scopes) = if level == self.enclosing.len() {
+            (&mut self.locals, &mut self.scopes)
+        } else {
+            let inner = &mut self.enclosing[level];
+            (&mut inner.locals, &mut inner.scopes)
+        };
+        let locals = locals.as_mut().unwrap();
+        let slot = locals.size;
+        locals.size += 1;
+        locals.captured.push(slot);
+        scopes[0].vars.insert(name.to_string(), DATA::LOCAL(slot));
+        self.enclosing[level - 1].captures.push((slot, outer_addr));
+        Some(DATA::LOCAL(slot))
+    }
+
+    //switches to a new frame holding args and returns the state of the code around it
+    fn enter_function(&mut self, args: &[String]) -> Enclosing {
+        let mut locals = Locals::default();
+        let mut scope = Scope::default();
+        for arg in args {
+            scope.vars.insert(arg.clone(), DATA::LOCAL(locals.size));
+            locals.size += 1;
+        }
+        Enclosing {
+            locals: self.locals.replace(locals),
+            scopes: std::mem::replace(&mut self.scopes, vec![scope]),
+            loops: std::mem::take(&mut self.loops),
+            captures: vec![],
+        }
+    }
+
+    fn leave_function(&mut self, outer: Enclosing) {
+        self.locals = outer.locals;
+        self.scopes = outer.scopes;
+        self.loops = outer.loops;
+    }
+
+    fn compile_lambda(
+        &mut self,
+        args: Vec<String>,
+        instruction: Vec<Instruction>,
+        addr: DATA,
+        //the name the lambda is bound to, visible in its own body
+        self_name: Option<String>,
+    ) -> Result<Vec<Operation>, CompileError> {
+        let label = self.new_label();
+        let arity = args.len();
+        let outer = self.enter_function(&args);
+        let self_slot = match self_name {
+            //filled by each call, so the value does not hold itself
+            Some(name) if !args.contains(&name) => match self.new_addr() {
+                DATA::LOCAL(slot) => {
+                    self.bind_var(name, DATA::LOCAL(slot));
+                    Some(slot)
+                }
+                _ => unreachable!(),
+            },
+            _ => None,
+        };
+        self.enclosing.push(outer);
+        //the body's statements move pos, errors after the lambda belong to the outer statement
+        let pos = self.pos;
+        let body = self.compile_function_body(instruction);
+        self.pos = pos;
+        let outer = self.enclosing.pop().unwrap();
+        let captures = outer
+            .captures
+            .iter()
+            .map(|(slot, value)| (*slot, pointer(value)))
+            .collect();
+        self.leave_function(outer);
+        let mut ops = vec![Operation::POINT {
+            name: DATA::Number(label),
+        }];
+        ops.extend(body?);
+        self.fuctions_programms.extend(ops);
+        Ok(vec![Operation::CLOSURE {
+            name: DATA::Number(label),
+            arity,
+            captures,
+            self_slot,
+            ret: addr,
+        }])
+    }
+
+    fn compile_instruction(&mut self, inst: Instruction) -> Result<Vec<Operation>, CompileError> {
+        match inst {
+            Instruction::HALT => Ok(vec![Operation::HALT]),
             Instruction::FUNCTION {
                 name,
                 args,
-                mut instruction,
+                instruction,
+                pos,
             } => {
-                let mut of = Vec::new();
-                let add = self.new_addr();
-                let func = FUNCTION {
-                    name: name.clone(),
-                    args: args
-                        .clone()
-                        .iter()
-                        .map(|x| {
-                            let mut x = x.clone();
-                            of.push(x.clone());
-                            x.push_str(&add.to_string());
-                            self.new_var(x.clone());
-                            x
-                        })
-                        .collect(),
-                    return_addr: self.new_addr(),
-                    addr: self.new_addr(),
+                self.pos = pos;
+                let func = match self.function_table().get_mut(&name) {
+                    //hoisted by this def
+                    Some(func) if !func.defined && func.pos == pos => {
+                        func.defined = true;
+                        func.clone()
+                    }
+                    //registered before the body is compiled so the function can call itself
+                    _ => self.declare_function(name.clone(), args.clone(), pos, true),
                 };
-                self.functions.insert(name.clone(), func.clone());
+                //the frame is discarded on return, so the body's own scope needs no drops
+                let outer = self.enter_function(&args);
+                //named functions capture nothing, they only see globals
+                let enclosing = std::mem::take(&mut self.enclosing);
+                let body = self.compile_function_body(instruction);
+                self.enclosing = enclosing;
+                self.leave_function(outer);
                 let mut ops = vec![Operation::POINT {
                     name: DATA::Number(func.addr),
                 }];
-                let last = instruction.pop().unwrap_or(Instruction::EXIT);
-                for i in instruction {
-                    let mut iops = self.compile_instruction(i.change_name(add, &mut of));
-                    ops.append(&mut iops);
+                ops.extend(body?);
+                self.fuctions_programms.extend(ops);
+                Ok(vec![])
+            }
+            Instruction::DROP { name, pos } => {
+                self.pos = pos;
+                let local = self
+                    .scopes
+                    .iter()
+                    .any(|scope| scope.vars.contains_key(&name));
+                //globals live as long as the top level wants them to
+                if self.locals.is_some() && !local && self.global(&name).is_some() {
+                    return Err(CompileError::DropGlobal { name, pos });
                 }
-                let last = last.change_name(add, &mut of);
-                match last {
-                    Instruction::RETURN { value } => {
-                        let addr = self.new_addr();
-                        let mut iops = value.to_addr(addr, self);
-                        iops.push(Operation::SET {
-                            name: DATA::POINTER(func.return_addr),
-                            value: DATA::POINTER(addr),
-                        });
-                        ops.extend(iops);
+                let removed = match self
+                    .scopes
+                    .iter_mut()
+                    .rev()
+                    .find(|scope| scope.vars.contains_key(&name))
+                {
+                    Some(scope) => scope.vars.remove(&name),
+                    None => {
+                        let addr = self.vars.remove(&name);
+                        //functions still refer to the address, a later let fills it again
+                        if let Some(addr) = addr {
+                            self.hoisted.insert(name.clone(), addr);
+                        }
+                        addr.map(DATA::Number)
                     }
-                    _ => {
-                        let mut iops = self.compile_instruction(last);
-                        ops.append(&mut iops);
+                };
+                match removed {
+                    Some(addr) => Ok(vec![Operation::DROP { name: addr }]),
+                    None => Err(CompileError::DropUndeclared { name, pos }),
+                }
+            }
+            Instruction::SET { name, value, pos } => {
+                self.pos = pos;
+                //bound after the value so `let x = x + 1` reads the outer x
+                let hoisted = match (&self.locals, self.scopes.is_empty()) {
+                    (None, true) => self.hoisted.get(&name).copied(),
+                    _ => None,
+                };
+                let global = match (&self.locals, self.scopes.is_empty()) {
+                    (None, true) => self.vars.get(&name).copied(),
+                    _ => None,
+                };
+                //a top level let keeps the address functions were compiled against
+                let addr = match hoisted.or(global) {
+                    Some(addr) => DATA::Number(addr),
+                    None => self.new_addr(),
+                };
+                let ops = match value {
+                    //top level globals are read when the lambda runs, other
+                    //variables are copied before they are bound, so a lambda kept in
+                    //one calls itself through a slot of its own
+                    Expression::Lambda(args, instruction)
+                        if self.locals.is_some() || !self.scopes.is_empty() =>
+                    {
+                        self.compile_lambda(args, instruction, addr.clone(), Some(name.clone()))?
                     }
+                    value => value.to_addr(addr.clone(), self)?,
                 };
-                ops.push(Operation::RET);
-                self.fuctions_programms.extend(ops);
-                vec![]
+                if hoisted.is_some() {
+                    self.hoisted.remove(&name);
+                }
+                self.bind_var(name, addr);
+                Ok(ops)
             }
-            Instruction::DROP { name } => {
-                let addr = self.vars[&name];
-                self.vars.remove(&name);
-                vec![Operation::DROP {
-                    name: DATA::Number(addr),
-                }]
-            }
-            Instruction::SET { name, value } => {
-                let addr = self.new_var(name.clone());
-                let ops = value.to_addr(addr, self);
-                ops
-            }
-            Instruction::ASSIGN { name, value } => {
-                let addr = self
-                    .vars
-                    .get(&name)
-                    .unwrap_or_else(|| {
-                        println!("var not defined: {}", name);
-                        std::process::exit(1);
-                    })
-                    .clone();
-                let ops = value.to_addr(addr, self);
-                ops
-            }
-            Instruction::PRINT { value } => {
+            Instruction::ASSIGN { name, value, pos } => {
+                self.pos = pos;
+                let addr = self.assignable(&name)?;
+                value.to_addr(addr, self)
+            }
+            Instruction::GLOBAL { name, pos } => {
+                self.pos = pos;
+                let Some(locals) = &mut self.locals else {
+                    return Err(CompileError::MisplacedGlobal { pos });
+                };
+                if !self.vars.contains_key(&name) && !self.hoisted.contains_key(&name) {
+                    return Err(CompileError::UndefinedVariable { name, pos });
+                }
+                locals.globals.push(name);
+                Ok(vec![])
+            }
+            Instruction::PRINT { value, pos } => {
+                self.pos = pos;
                 let addr = self.new_addr();
-                let mut ops = value.to_addr(addr, self);
+                let mut ops = value.to_addr(addr.clone(), self)?;
                 ops.push(Operation::PRINT {
-                    value: DATA::POINTER(addr),
+                    value: pointer(&addr),
                 });
-                ops
+                Ok(ops)
             }
-            Instruction::LOOP { instruction } => {
+            Instruction::EXPRESSION { value, pos } => {
+                self.pos = pos;
                 let addr = self.new_addr();
+                let mut ops = value.to_addr(addr.clone(), self)?;
+                //a called function need not return anything when its result is unused
+                match ops.last() {
+                    Some(Operation::RESULT { .. }) => {
+                        ops.pop();
+                    }
+                    _ => ops.push(Operation::DROP { name: addr }),
+                }
+                Ok(ops)
+            }
+            Instruction::LOOP { instruction } => {
+                let addr = self.new_label();
+                let end = self.new_label();
                 let mut ops = vec![Operation::POINT {
                     name: DATA::Number(addr),
                 }];
-                for i in instruction {
-                    let mut iops = self.compile_instruction(i);
-                    ops.append(&mut iops);
-                }
+                ops.extend(self.compile_loop_body(instruction, addr, end)?);
                 ops.push(Operation::JUMP {
                     name: DATA::Number(addr),
                 });
-                ops
+                ops.push(Operation::POINT {
+                    name: DATA::Number(end),
+                });
+                Ok(ops)
             }
-            Instruction::BLOCK { instruction } => {
-                let mut ops = Vec::new();
-                for i in instruction {
-                    let mut iops = self.compile_instruction(i);
-                    ops.append(&mut iops);
+            Instruction::BLOCK { instruction } => self.compile_scoped(instruction),
+            Instruction::STRUCT { name, fields, pos } => {
+                match self.structs.get_mut(&name) {
+                    //hoisted by this statement
+                    Some(def) if !def.defined && def.pos == pos => def.defined = true,
+                    _ => {
+                        self.structs.insert(
+                            name,
+                            STRUCT {
+                                fields,
+                                defined: true,
+                                pos,
+                            },
+                        );
+                    }
                 }
-                ops
+                Ok(vec![])
+            }
+            Instruction::SET_FIELD {
+                target,
+                field,
+                value,
+                pos,
+            } => {
+                self.pos = pos;
+                let target_addr = self.new_addr();
+                let value_addr = self.new_addr();
+                let mut ops = target.to_addr(target_addr.clone(), self)?;
+                ops.extend(value.to_addr(value_addr.clone(), self)?);
+                ops.push(Operation::SET_FIELD {
+                    name: pointer(&target_addr),
+                    field,
+                    value: pointer(&value_addr),
+                });
+                Ok(ops)
+            }
+            Instruction::SET_INDEX {
+                target,
+                index,
+                value,
+                pos,
+            } => {
+                self.pos = pos;
+                let target_addr = self.new_addr();
+                let index_addr = self.new_addr();
+                let value_addr = self.new_addr();
+                let mut ops = target.to_addr(target_addr.clone(), self)?;
+                ops.extend(index.to_addr(index_addr.clone(), self)?);
+                ops.extend(value.to_addr(value_addr.clone(), self)?);
+                ops.push(Operation::SET_INDEX {
+                    name: pointer(&target_addr),
+                    index: pointer(&index_addr),
+                    value: pointer(&value_addr),
+                });
+                Ok(ops)
+            }
+            //walks an index over the list, re-reading its length each time around
+            Instruction::FOR {
+                name,
+                iterable,
+                instruction,
+                pos,
+            } => {
+                self.pos = pos;
+                let list = self.new_addr();
+                let index = self.new_addr();
+                let length = self.new_addr();
+                let done = self.new_addr();
+                let start = self.new_label();
+                let end = self.new_label();
+                let mut ops = iterable.to_addr(list.clone(), self)?;
+                ops.push(Operation::SET {
+                    name: index.clone(),
+                    value: DATA::Number(0),
+                });
+                ops.push(Operation::POINT {
+                    name: DATA::Number(start),
+                });
+                ops.push(Operation::BUILTIN {
+                    func: Builtin::LEN,
+                    args: vec![pointer(&list)],
+                    ret: length.clone(),
+                });
+                ops.push(Operation::GREATER_EQ {
+                    name: pointer(&index),
+                    value: pointer(&length),
+                    ret: done.clone(),
+                });
+                ops.push(Operation::JUMP_IF {
+                    name: DATA::Number(end),
+                    condition: pointer(&done),
+                });
+                //the loop variable lives in a scope around the body
+                self.scopes.push(Scope::default());
+                let item = self.new_addr();
+                self.bind_var(name, item.clone());
+                ops.push(Operation::INDEX {
+                    name: pointer(&list),
+                    index: pointer(&index),
+                    ret: item,
+                });
+                ops.push(Operation::ADD {
+                    name: pointer(&index),
+                    value: DATA::Number(1),
+                    ret: index.clone(),
+                });
+                let body = self.compile_loop_body(instruction, start, end);
+                let scope = self.scopes.pop().unwrap();
+                ops.extend(body?);
+                ops.push(Operation::JUMP {
+                    name: DATA::Number(start),
+                });
+                ops.push(Operation::POINT {
+                    name: DATA::Number(end),
+                });
+                ops.extend(Compiler::drops(&[scope]));
+                Ok(ops)
             }
             Instruction::IF {
                 condition,
                 instruction,
+                else_instruction,
+                pos,
             } => {
+                self.pos = pos;
                 let addr = self.new_addr();
-                let mut ops = Expression::NOT(Box::new(condition)).to_addr(addr, self);
-                let jump_addr = self.new_addr();
+                let mut ops = Expression::NOT(Box::new(condition)).to_addr(addr.clone(), self)?;
+                let jump_addr = self.new_label();
                 ops.push(Operation::JUMP_IF {
                     name: DATA::Number(jump_addr),
-                    condition: DATA::POINTER(addr),
+                    condition: pointer(&addr),
                 });
-                for i in instruction {
-                    let mut iops = self.compile_instruction(i);
-                    ops.append(&mut iops);
+                ops.extend(self.compile_scoped(instruction)?);
+                if else_instruction.is_empty() {
+                    ops.push(Operation::POINT {
+                        name: DATA::Number(jump_addr),
+                    });
+                    return Ok(ops);
                 }
+                let end_addr = self.new_label();
+                ops.push(Operation::JUMP {
+                    name: DATA::Number(end_addr),
+                });
                 ops.push(Operation::POINT {
                     name: DATA::Number(jump_addr),
                 });
-                ops
+                ops.extend(self.compile_scoped(else_instruction)?);
+                ops.push(Operation::POINT {
+                    name: DATA::Number(end_addr),
+                });
+                Ok(ops)
             }
             Instruction::WHILE {
                 condition,
                 instruction,
+                pos,
             } => {
-                let jump_start: i32 = self.new_addr();
-                let jump_end: i32 = self.new_addr();
+                self.pos = pos;
+                let jump_start: i64 = self.new_label();
+                let jump_end: i64 = self.new_label();
                 let cond_addr = self.new_addr();
                 let mut ops = vec![Operation::POINT {
                     name: DATA::Number(jump_start),
                 }];
-                ops.extend(Expression::NOT(Box::new(condition)).to_addr(cond_addr, self));
+                ops.extend(Expression::NOT(Box::new(condition)).to_addr(cond_addr.clone(), self)?);
                 ops.push(Operation::JUMP_IF {
                     name: DATA::Number(jump_end),
-                    condition: DATA::POINTER(cond_addr),
+                    condition: pointer(&cond_addr),
                 });
-                for i in instruction {
-                    let mut iops = self.compile_instruction(i);
-                    ops.append(&mut iops);
-                }
+                ops.extend(self.compile_loop_body(instruction, jump_start, jump_end)?);
                 ops.push(Operation::JUMP {
                     name: DATA::Number(jump_start),
                 });
                 ops.push(Operation::POINT {
                     name: DATA::Number(jump_end),
                 });
-                ops
+                Ok(ops)
             }
-            Instruction::RETURN { value:_ } => {
-                panic!("return outside function last")
+            Instruction::BREAK { pos } => match self.loops.last() {
+                Some((_, end, depth)) => {
+                    let mut ops = Compiler::drops(&self.scopes[*depth..]);
+                    ops.push(Operation::JUMP {
+                        name: DATA::Number(*end),
+                    });
+                    Ok(ops)
+                }
+                None => Err(CompileError::MisplacedBreak { pos }),
+            },
+            Instruction::CONTINUE { pos } => match self.loops.last() {
+                Some((start, _, depth)) => {
+                    let mut ops = Compiler::drops(&self.scopes[*depth..]);
+                    ops.push(Operation::JUMP {
+                        name: DATA::Number(*start),
+                    });
+                    Ok(ops)
+                }
+                None => Err(CompileError::MisplacedContinue { pos }),
+            },
+            Instruction::RETURN { value, pos } => {
+                self.pos = pos;
+                if self.locals.is_none() {
+                    return Err(CompileError::MisplacedReturn { pos });
+                }
+                //RETURN closes the frame itself, so it can leave from any depth
+                let addr = self.new_addr();
+                let mut ops = value.to_addr(addr.clone(), self)?;
+                ops.push(Operation::RETURN {
+                    value: pointer(&addr),
+                });
+                Ok(ops)
             }
-            Instruction::EXIT => {
-                vec![Operation::RET]
+            //stops the program, also from inside a function
+            Instruction::EXIT => Ok(vec![Operation::HALT]),
+        }
+    }
+
+    fn compile_loop_body(
+        &mut self,
+        instruction: Vec<Instruction>,
+        start: i64,
+        end: i64,
+    ) -> Result<Vec<Operation>, CompileError> {
+        self.loops.push((start, end, self.scopes.len()));
+        let ops = self.compile_scoped(instruction)?;
+        self.loops.pop();
+        Ok(ops)
+    }
+
+    fn compile_function_body(
+        &mut self,
+        instruction: Vec<Instruction>,
+    ) -> Result<Vec<Operation>, CompileError> {
+        //defs inside a function are private to it
+        self.nested.push(HashMap::new());
+        let ops = self.compile_hoisted(instruction);
+        self.nested.pop();
+        let mut ops = ops?;
+        //falling off the end returns without a value
+        ops.push(Operation::RET);
+        Ok(ops)
+    }
+
+    fn compile_hoisted(
+        &mut self,
+        instruction: Vec<Instruction>,
+    ) -> Result<Vec<Operation>, CompileError> {
+        self.hoist(&instruction)?;
+        let mut ops = vec![];
+        for i in instruction {
+            let mut iops = self.compile_instruction(i)?;
+            ops.append(&mut iops);
+        }
+        Ok(ops)
+    }
+
+    //declares the defs and structs of a unit or function body, including those
+    //in its blocks and loops, so calls may come before the definition;
+    //the bodies of nested defs are hoisted when they are compiled
+    fn hoist(&mut self, instructions: &[Instruction]) -> Result<(), CompileError> {
+        for i in instructions {
+            match i {
+                Instruction::FUNCTION {
+                    name, args, pos, ..
+                } => {
+                    //a def from an earlier unit may be replaced, one from this unit may not
+                    match self.function_table().get(name) {
+                        Some(func) if !func.defined => {
+                            return Err(CompileError::DuplicateFunction {
+                                name: name.clone(),
+                                pos: *pos,
+                                defined_at: func.pos,
+                            })
+                        }
+                        _ => {}
+                    }
+                    self.declare_function(name.clone(), args.clone(), *pos, false);
+                }
+                Instruction::STRUCT { name, fields, pos } => {
+                    //like defs, a struct from an earlier unit may be replaced
+                    match self.structs.get(name) {
+                        Some(def) if !def.defined => {
+                            return Err(CompileError::DuplicateStruct {
+                                name: name.clone(),
+                                pos: *pos,
+                                defined_at: def.pos,
+                            })
+                        }
+                        _ => {}
+                    }
+                    self.structs.insert(
+                        name.clone(),
+                        STRUCT {
+                            fields: fields.clone(),
+                            defined: false,
+                            pos: *pos,
+                        },
+                    );
+                }
+                Instruction::BLOCK { instruction }
+                | Instruction::LOOP { instruction }
+                | Instruction::WHILE { instruction, .. }
+                | Instruction::FOR { instruction, .. } => self.hoist(instruction)?,
+                Instruction::IF {
+                    instruction,
+                    else_instruction,
+                    ..
+                } => {
+                    self.hoist(instruction)?;
+                    self.hoist(else_instruction)?;
+                }
+                _ => {}
             }
         }
+        Ok(())
     }
 
-    fn compile_instructions(&mut self) {
+    fn compile_instructions(&mut self) -> Result<(), CompileError> {
+        self.hoist(&self.instructions.clone())?;
+        //globals declared anywhere at the top level of the unit are visible to its functions
+        for i in self.instructions.clone() {
+            match i {
+                Instruction::SET { name, .. }
+                    if !self.vars.contains_key(&name) && !self.hoisted.contains_key(&name) =>
+                {
+                    let addr = self.new_label();
+                    self.hoisted.insert(name, addr);
+                }
+                _ => {}
+            }
+        }
         for i in self.instructions.clone() {
-            let ops = self.compile_instruction(i);
+            let ops = self.compile_instruction(i)?;
             self.program.extend(ops);
         }
-        let end = self.new_addr();
-        self.program.push(Operation::JUMP { name: DATA::Number(end) });
+        let end = self.new_label();
+        self.program.push(Operation::JUMP {
+            name: DATA::Number(end),
+        });
         self.program.extend(self.fuctions_programms.clone());
-        self.program.push(Operation::POINT { name: DATA::Number(end) });
+        self.program.push(Operation::POINT {
+            name: DATA::Number(end),
+        });
         self.program.push(Operation::HALT);
+        Ok(())
     }
 }
@@ -1,26 +1,195 @@
-use std::collections::HashMap;
+use crate::builtin::Builtin;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::rc::Rc;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum DATA {
-    Number(i32),
+    Number(i64),
+    Float(f64),
     Bool(bool),
-    POINTER(i32),
+    POINTER(i64),
+    LOCAL(i64),
     String(String),
+    //lists are shared, copying one copies the reference
+    List(Rc<RefCell<Vec<DATA>>>),
+    //maps are shared like lists
+    Map(Rc<RefCell<BTreeMap<Key, DATA>>>),
+    Record(Rc<RefCell<Record>>),
+    Function(Rc<Function>),
+}
+
+//a function value, captures are copied into the given frame slots on every call
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub addr: i64,
+    pub arity: usize,
+    pub captures: Vec<(i64, DATA)>,
+    //slot that holds the function itself during a call, for lambdas calling their own name
+    pub self_slot: Option<i64>,
+}
+
+//an instance of a struct, fields are kept in declaration order
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    pub name: String,
+    pub fields: Vec<(String, DATA)>,
+}
+
+//the values a map can be keyed by
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Key {
+    Number(i64),
+    String(String),
+}
+
+impl Key {
+    pub fn to_data(&self) -> DATA {
+        match self {
+            Key::Number(n) => DATA::Number(*n),
+            Key::String(s) => DATA::String(s.clone()),
+        }
+    }
 }
 
 impl DATA {
+    pub fn list(items: Vec<DATA>) -> DATA {
+        DATA::List(Rc::new(RefCell::new(items)))
+    }
+
+    pub(crate) fn to_key(&self) -> Result<Key, RuntimeErrorKind> {
+        match self {
+            DATA::Number(n) => Ok(Key::Number(*n)),
+            DATA::String(s) => Ok(Key::String(s.clone())),
+            _ => Err(RuntimeErrorKind::TypeError(format!(
+                "map keys must be integers or strings, found {}",
+                self.fancy_string()
+            ))),
+        }
+    }
+
+    fn index(&self, index: &DATA) -> Result<DATA, RuntimeErrorKind> {
+        let value = match self {
+            DATA::Map(map) => {
+                return match map.borrow().get(&index.to_key()?) {
+                    Some(value) => Ok(value.clone()),
+                    None => Err(RuntimeErrorKind::MissingKey(index.fancy_string())),
+                }
+            }
+            DATA::List(items) => usize::try_from(index.to_i64()?)
+                .ok()
+                .and_then(|i| items.borrow().get(i).cloned()),
+            DATA::String(s) => usize::try_from(index.to_i64()?)
+                .ok()
+                .and_then(|i| s.chars().nth(i))
+                .map(|c| DATA::String(c.to_string())),
+            _ => {
+                return Err(RuntimeErrorKind::TypeError(format!(
+                    "cannot index {}",
+                    self.fancy_string()
+                )))
+            }
+        };
+        value.ok_or(RuntimeErrorKind::IndexOutOfBounds(index.to_i64()?))
+    }
+
+    fn set_index(&self, index: &DATA, value: DATA) -> Result<(), RuntimeErrorKind> {
+        match self {
+            DATA::Map(map) => {
+                map.borrow_mut().insert(index.to_key()?, value);
+            }
+            DATA::List(items) => {
+                let i = index.to_i64()?;
+                let mut items = items.borrow_mut();
+                match usize::try_from(i).ok().and_then(|i| items.get_mut(i)) {
+                    Some(item) => *item = value,
+                    None => return Err(RuntimeErrorKind::IndexOutOfBounds(i)),
+                }
+            }
+            _ => {
+                return Err(RuntimeErrorKind::TypeError(format!(
+                    "cannot assign to an index of {}",
+                    self.fancy_string()
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    fn field(&self, field: &str) -> Result<DATA, RuntimeErrorKind> {
+        let DATA::Record(record) = self else {
+            return Err(RuntimeErrorKind::TypeError(format!(
+                "cannot read field `{}` of {}",
+                field,
+                self.fancy_string()
+            )));
+        };
+        let record = record.borrow();
+        match record.fields.iter().find(|(name, _)| name == field) {
+            Some((_, value)) => Ok(value.clone()),
+            None => Err(RuntimeErrorKind::MissingField(
+                record.name.clone(),
+                field.to_string(),
+            )),
+        }
+    }
+
+    fn set_field(&self, field: &str, value: DATA) -> Result<(), RuntimeErrorKind> {
+        let DATA::Record(record) = self else {
+            return Err(RuntimeErrorKind::TypeError(format!(
+                "cannot assign to field `{}` of {}",
+                field,
+                self.fancy_string()
+            )));
+        };
+        let mut record = record.borrow_mut();
+        let name = record.name.clone();
+        match record.fields.iter_mut().find(|(name, _)| name == field) {
+            Some((_, old)) => *old = value,
+            None => return Err(RuntimeErrorKind::MissingField(name, field.to_string())),
+        }
+        Ok(())
+    }
+
+    fn is_number(&self) -> bool {
+        matches!(self, DATA::Number(_) | DATA::Float(_) | DATA::Bool(_))
+    }
+
     fn to_string(&self) -> String {
         match self {
             DATA::Number(n) => n.to_string(),
+            DATA::Float(f) => format!("{:?}", f),
             DATA::Bool(b) => b.to_string(),
             DATA::POINTER(p) => p.to_string(),
+            DATA::LOCAL(p) => p.to_string(),
             DATA::String(s) => s.clone(),
+            DATA::List(_) | DATA::Map(_) | DATA::Record(_) | DATA::Function(_) => {
+                self.fancy_string()
+            }
         }
     }
 
-    fn fancy_string(&self) -> String {
-        match self {
+    pub(crate) fn fancy_string(&self) -> String {
+        self.fancy(&mut vec![])
+    }
+
+    //containers can hold themselves, one already being printed shows as `...`
+    fn fancy(&self, open: &mut Vec<*const ()>) -> String {
+        let ptr = match self {
+            DATA::List(items) => Rc::as_ptr(items) as *const (),
+            DATA::Map(map) => Rc::as_ptr(map) as *const (),
+            DATA::Record(record) => Rc::as_ptr(record) as *const (),
+            _ => std::ptr::null(),
+        };
+        if !ptr.is_null() && open.contains(&ptr) {
+            return "...".to_string();
+        }
+        open.push(ptr);
+        let s = match self {
             DATA::Number(n) => n.to_string(),
+            DATA::Float(f) => format!("{:?}", f),
             DATA::Bool(b) => {
                 if *b {
                     "true".to_string()
@@ -29,22 +198,119 @@ impl DATA {
                 }
             }
             DATA::POINTER(p) => format!("&{}", p),
+            DATA::LOCAL(p) => format!("${}", p),
             DATA::String(s) => format!("\"{}\"", s),
+            DATA::List(items) => {
+                let items = items.borrow();
+                let items = items.iter().map(|x| x.fancy(open)).collect::<Vec<_>>();
+                format!("[{}]", items.join(", "))
+            }
+            DATA::Map(map) => {
+                let map = map.borrow();
+                let entries = map
+                    .iter()
+                    .map(|(k, v)| format!("{}: {}", k.to_data().fancy_string(), v.fancy(open)))
+                    .collect::<Vec<_>>();
+                format!("{{{}}}", entries.join(", "))
+            }
+            DATA::Record(record) => {
+                let record = record.borrow();
+                let fields = record
+                    .fields
+                    .iter()
+                    .map(|(k, v)| format!("{}: {}", k, v.fancy(open)))
+                    .collect::<Vec<_>>();
+                format!("{} {{ {} }}", record.name, fields.join(", "))
+            }
+            DATA::Function(_) => "<fn>".to_string(),
+        };
+        open.pop();
+        s
+    }
+
+    //structural equality that terminates on containers holding themselves;
+    //functions are only equal to themselves
+    fn equals(&self, other: &DATA, open: &mut Vec<(*const (), *const ())>) -> bool {
+        let pair = match (self, other) {
+            (DATA::List(a), DATA::List(b)) => {
+                (Rc::as_ptr(a) as *const (), Rc::as_ptr(b) as *const ())
+            }
+            (DATA::Map(a), DATA::Map(b)) => {
+                (Rc::as_ptr(a) as *const (), Rc::as_ptr(b) as *const ())
+            }
+            (DATA::Record(a), DATA::Record(b)) => {
+                (Rc::as_ptr(a) as *const (), Rc::as_ptr(b) as *const ())
+            }
+            (DATA::Function(a), DATA::Function(b)) => return Rc::ptr_eq(a, b),
+            //numbers compare by value across ints, floats and bools
+            (DATA::Float(_), _) | (_, DATA::Float(_)) if self.is_number() && other.is_number() => {
+                return self.to_f64().ok() == other.to_f64().ok()
+            }
+            _ if self.is_number() && other.is_number() => {
+                return self.to_i64().ok() == other.to_i64().ok()
+            }
+            _ => return self == other,
+        };
+        //the same container, or a pair already being compared further up
+        if pair.0 == pair.1 || open.contains(&pair) {
+            return true;
         }
+        open.push(pair);
+        let result = match (self, other) {
+            (DATA::List(a), DATA::List(b)) => {
+                let (a, b) = (a.borrow(), b.borrow());
+                a.len() == b.len() && a.iter().zip(b.iter()).all(|(x, y)| x.equals(y, open))
+            }
+            (DATA::Map(a), DATA::Map(b)) => {
+                let (a, b) = (a.borrow(), b.borrow());
+                a.len() == b.len()
+                    && a.iter()
+                        .zip(b.iter())
+                        .all(|((ka, va), (kb, vb))| ka == kb && va.equals(vb, open))
+            }
+            (DATA::Record(a), DATA::Record(b)) => {
+                let (a, b) = (a.borrow(), b.borrow());
+                a.name == b.name
+                    && a.fields.len() == b.fields.len()
+                    && a.fields
+                        .iter()
+                        .zip(b.fields.iter())
+                        .all(|((ka, va), (kb, vb))| ka == kb && va.equals(vb, open))
+            }
+            _ => unreachable!(),
+        };
+        open.pop();
+        result
     }
 
-    fn to_i32(&self) -> i32 {
+    pub(crate) fn to_i64(&self) -> Result<i64, RuntimeErrorKind> {
         match self {
-            DATA::Number(n) => *n,
+            DATA::Number(n) => Ok(*n),
             DATA::Bool(b) => {
                 if *b {
-                    1
+                    Ok(1)
                 } else {
-                    0
+                    Ok(0)
                 }
             }
-            DATA::POINTER(p) => *p,
-            DATA::String(_s) => panic!("Cannot convert String to i32"),
+            DATA::POINTER(p) => Ok(*p),
+            DATA::LOCAL(p) => Ok(*p),
+            _ => Err(RuntimeErrorKind::TypeError(format!(
+                "expected an integer, found {}",
+                self.fancy_string()
+            ))),
+        }
+    }
+
+    fn to_f64(&self) -> Result<f64, RuntimeErrorKind> {
+        match self {
+            DATA::Float(f) => Ok(*f),
+            DATA::Number(n) => Ok(*n as f64),
+            DATA::Bool(b) => Ok(if *b { 1.0 } else { 0.0 }),
+            _ => Err(RuntimeErrorKind::TypeError(format!(
+                "expected a number, found {}",
+                self.fancy_string()
+            ))),
         }
     }
 
@@ -52,81 +318,268 @@ impl DATA {
         if s.starts_with("\"") && s.ends_with("\"") {
             DATA::String(s[1..s.len() - 1].to_string())
         } else if s.starts_with("&") {
-            DATA::POINTER(s[1..].parse::<i32>().unwrap())
+            DATA::POINTER(s[1..].parse::<i64>().unwrap())
+        } else if s.starts_with("$") {
+            DATA::LOCAL(s[1..].parse::<i64>().unwrap())
         } else if s == "true" {
             DATA::Bool(true)
         } else if s == "false" {
             DATA::Bool(false)
+        } else if s.contains('.') {
+            DATA::Float(s.parse::<f64>().unwrap())
         } else {
-            DATA::Number(s.parse::<i32>().unwrap())
+            DATA::Number(s.parse::<i64>().unwrap())
         }
     }
 
-    fn get(&self, int: &mut Interpreter) -> DATA {
+    fn get(&self, int: &mut Interpreter) -> Result<DATA, RuntimeErrorKind> {
         match self {
-            DATA::POINTER(p) => int
-                .data
-                .get(p)
-                .expect(&format!("NULLPOINTER:{}", p))
-                .clone()
-                .get(int),
-            _ => self.clone(),
+            DATA::POINTER(p) => match int.data.get(p) {
+                Some(data) => data.clone().get(int),
+                None => Err(RuntimeErrorKind::NullPointer(*p)),
+            },
+            DATA::LOCAL(p) => match int.frame().locals.get(p) {
+                Some(data) => data.clone().get(int),
+                None => Err(RuntimeErrorKind::NullLocal(*p)),
+            },
+            _ => Ok(self.clone()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuntimeErrorKind {
+    NullPointer(i64),
+    NullLocal(i64),
+    UnknownLabel(i64),
+    TypeError(String),
+    DivisionByZero,
+    Overflow(String),
+    IndexOutOfBounds(i64),
+    MissingKey(String),
+    MissingField(String, String),
+    //expected and given number of arguments of an indirect call
+    ArityMismatch(usize, usize),
+    InvalidArgument(String),
+    EmptyCallStack,
+    NoReturnValue,
+}
+
+impl fmt::Display for RuntimeErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RuntimeErrorKind::NullPointer(p) => write!(f, "null pointer &{}", p),
+            RuntimeErrorKind::NullLocal(p) => write!(f, "null pointer ${}", p),
+            RuntimeErrorKind::UnknownLabel(l) => write!(f, "unknown jump label {}", l),
+            RuntimeErrorKind::TypeError(s) => write!(f, "type error: {}", s),
+            RuntimeErrorKind::DivisionByZero => write!(f, "division by zero"),
+            RuntimeErrorKind::Overflow(op) => write!(f, "integer overflow in {}", op),
+            RuntimeErrorKind::IndexOutOfBounds(i) => write!(f, "index {} out of bounds", i),
+            RuntimeErrorKind::MissingKey(k) => write!(f, "key {} is not in the map", k),
+            RuntimeErrorKind::MissingField(name, field) => {
+                write!(f, "{} has no field `{}`", name, field)
+            }
+            RuntimeErrorKind::ArityMismatch(expected, found) => write!(
+                f,
+                "function takes {} argument(s) but {} were given",
+                expected, found
+            ),
+            RuntimeErrorKind::InvalidArgument(s) => write!(f, "invalid argument: {}", s),
+            RuntimeErrorKind::EmptyCallStack => write!(f, "return with an empty call stack"),
+            RuntimeErrorKind::NoReturnValue => write!(f, "function did not return a value"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RuntimeError {
+    pub kind: RuntimeErrorKind,
+    pub pc: usize,
+    //boxed to keep Result<_, RuntimeError> small
+    pub operation: Box<Operation>,
+    //pc of every active call site, outermost first
+    pub call_stack: Vec<usize>,
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "runtime error: {}\n  at {}: {}",
+            self.kind,
+            self.pc,
+            easy::fancy_string(vec![(*self.operation).clone()])
+        )?;
+        for pc in self.call_stack.iter().rev() {
+            write!(f, "\n  called from {}", pc)?;
         }
+        Ok(())
     }
 }
 
+impl std::error::Error for RuntimeError {}
+
 #[derive(Debug, Clone)]
 pub enum Operation {
-    POINT { name: DATA },
-    SET { name: DATA, value: DATA },
-    JUMP { name: DATA },
-    PRINT { value: DATA },
-    CALL { name: DATA },
+    POINT {
+        name: DATA,
+    },
+    SET {
+        name: DATA,
+        value: DATA,
+    },
+    JUMP {
+        name: DATA,
+    },
+    PRINT {
+        value: DATA,
+    },
+    CALL {
+        name: DATA,
+    },
+    ARG {
+        value: DATA,
+    },
+    RESULT {
+        name: DATA,
+    },
+    RETURN {
+        value: DATA,
+    },
     RET,
     HALT,
-    JUMP_IF { name: DATA, condition: DATA },
-    EQ { name: DATA, value: DATA, ret: DATA },
-    GREATER { name: DATA, value: DATA, ret: DATA },
-    NOT { name: DATA, ret: DATA },
-    ADD { name: DATA, value: DATA, ret: DATA },
-    SUB { name: DATA, value: DATA, ret: DATA },
-    MUL { name: DATA, value: DATA, ret: DATA },
-    DIV { name: DATA, value: DATA, ret: DATA },
+    JUMP_IF {
+        name: DATA,
+        condition: DATA,
+    },
+    EQ {
+        name: DATA,
+        value: DATA,
+        ret: DATA,
+    },
+    GREATER {
+        name: DATA,
+        value: DATA,
+        ret: DATA,
+    },
+    GREATER_EQ {
+        name: DATA,
+        value: DATA,
+        ret: DATA,
+    },
+    NOT {
+        name: DATA,
+        ret: DATA,
+    },
+    ADD {
+        name: DATA,
+        value: DATA,
+        ret: DATA,
+    },
+    SUB {
+        name: DATA,
+        value: DATA,
+        ret: DATA,
+    },
+    MUL {
+        name: DATA,
+        value: DATA,
+        ret: DATA,
+    },
+    DIV {
+        name: DATA,
+        value: DATA,
+        ret: DATA,
+    },
+    MOD {
+        name: DATA,
+        value: DATA,
+        ret: DATA,
+    },
     NOP,
-    DROP { name: DATA },
+    DROP {
+        name: DATA,
+    },
+    BUILTIN {
+        func: Builtin,
+        args: Vec<DATA>,
+        ret: DATA,
+    },
+    LIST {
+        items: Vec<DATA>,
+        ret: DATA,
+    },
+    INDEX {
+        name: DATA,
+        index: DATA,
+        ret: DATA,
+    },
+    SET_INDEX {
+        name: DATA,
+        index: DATA,
+        value: DATA,
+    },
+    MAP {
+        entries: Vec<(DATA, DATA)>,
+        ret: DATA,
+    },
+    RECORD {
+        name: String,
+        fields: Vec<(String, DATA)>,
+        ret: DATA,
+    },
+    GET_FIELD {
+        name: DATA,
+        field: String,
+        ret: DATA,
+    },
+    SET_FIELD {
+        name: DATA,
+        field: String,
+        value: DATA,
+    },
+    //creates a function value for the code at label name
+    CLOSURE {
+        name: DATA,
+        arity: usize,
+        captures: Vec<(i64, DATA)>,
+        self_slot: Option<i64>,
+        ret: DATA,
+    },
+    //calls the function value in name
+    CALL_VALUE {
+        name: DATA,
+    },
+}
+
+//the locals of one function call, addressed by DATA::LOCAL
+#[derive(Debug, Clone, Default)]
+pub struct Frame {
+    pub locals: HashMap<i64, DATA>,
 }
 
 #[derive(Debug, Clone)]
 pub struct Interpreter {
-    pub data: HashMap<i32, DATA>,
-    pub points: HashMap<i32, usize>,
+    pub data: HashMap<i64, DATA>,
+    pub points: HashMap<i64, usize>,
     pub programms: Vec<Operation>,
     pub call_stack: Vec<usize>,
+    pub frames: Vec<Frame>,
+    pub args: Vec<DATA>,
+    pub result: Option<DATA>,
+    //wrap on integer overflow instead of raising a runtime error
+    pub wrapping: bool,
+    //when set, printed lines are collected here instead of written to stdout
+    pub output: Option<Vec<String>>,
 }
 
+//the checked and wrapping versions of an integer operation
+type IntOp = (fn(i64, i64) -> Option<i64>, fn(i64, i64) -> i64);
+
 impl Interpreter {
-    pub fn run(p: Vec<Operation>) {
+    pub fn run(p: Vec<Operation>) -> Result<(), RuntimeError> {
         let mut interpreter = Interpreter::new();
-        interpreter.programms = p;
-        interpreter.call_stack.push(0);
-        let mut pos = 0;
-        interpreter.programms = interpreter
-            .programms
-            .iter()
-            .filter(|x| match x {
-                Operation::POINT { name } => {
-                    interpreter.points.insert(name.to_i32(), pos);
-                    pos += 1;
-                    false
-                }
-                _ => {
-                    pos += 1;
-                    true
-                }
-            })
-            .map(|x| x.clone())
-            .collect();
-        while interpreter.tick() {}
+        interpreter.continues_run(p)
     }
 
     pub fn new() -> Interpreter {
@@ -135,138 +588,422 @@ impl Interpreter {
             points: HashMap::new(),
             programms: Vec::new(),
             call_stack: Vec::new(),
+            frames: vec![Frame::default()],
+            args: Vec::new(),
+            result: None,
+            wrapping: false,
+            output: None,
         }
     }
 
-    pub fn continues_run(&mut self, p: Vec<Operation>) {
-        self.programms = p;
-        self.call_stack.push(0);
-        let mut pos = 0;
-        self.programms = self
-            .programms
-            .iter()
-            .filter(|x| match x {
-                Operation::POINT { name } => {
-                    self.points.insert(name.to_i32(), pos);
-                    pos += 1;
-                    false
+    pub fn continues_run(&mut self, p: Vec<Operation>) -> Result<(), RuntimeError> {
+        self.programms = Vec::new();
+        for operation in p {
+            match operation {
+                Operation::POINT { ref name } => {
+                    let pos = name.to_i64().map_err(|kind| RuntimeError {
+                        kind,
+                        pc: self.programms.len(),
+                        operation: Box::new(operation.clone()),
+                        call_stack: vec![],
+                    })?;
+                    self.points.insert(pos, self.programms.len());
                 }
-                _ => {
-                    pos += 1;
-                    true
-                }
-            })
-            .map(|x| x.clone())
-            .collect();
-        while self.tick() {}
+                _ => self.programms.push(operation),
+            }
+        }
+        self.call_stack = vec![0];
+        self.frames.truncate(1);
+        self.args = Vec::new();
+        while self.tick()? {}
+        Ok(())
+    }
+
+    fn frame(&self) -> &Frame {
+        self.frames.last().unwrap()
+    }
+
+    fn store(&mut self, name: &DATA, value: DATA) -> Result<(), RuntimeErrorKind> {
+        match name {
+            DATA::LOCAL(p) => {
+                self.frames.last_mut().unwrap().locals.insert(*p, value);
+            }
+            _ => {
+                let pos = name.get(self)?.to_i64()?;
+                self.data.insert(pos, value);
+            }
+        }
+        Ok(())
+    }
+
+    fn ret(&mut self) -> Result<(), RuntimeErrorKind> {
+        if self.call_stack.len() < 2 || self.frames.len() < 2 {
+            return Err(RuntimeErrorKind::EmptyCallStack);
+        }
+        self.frames.pop();
+        self.call_stack.pop();
+        Ok(())
     }
 
-    pub fn tick(&mut self) -> bool {
-        let index = self.call_stack.pop().unwrap();
-        if index > self.programms.len() - 1 {
-            return false;
+    fn label(&self, pos: i64) -> Result<usize, RuntimeErrorKind> {
+        match self.points.get(&pos) {
+            Some(index) => Ok(*index),
+            None => Err(RuntimeErrorKind::UnknownLabel(pos)),
+        }
+    }
+
+    pub fn tick(&mut self) -> Result<bool, RuntimeError> {
+        let index = match self.call_stack.pop() {
+            Some(index) => index,
+            None => return Ok(false),
+        };
+        if index >= self.programms.len() {
+            return Ok(false);
         }
-        let operation = self.programms[index].clone();
         self.call_stack.push(index + 1);
+        //moved out while executing so the operation can be borrowed, execute never reads it
+        let programms = std::mem::take(&mut self.programms);
+        let result = self.execute(index, &programms[index]);
+        self.programms = programms;
+        result.map_err(|kind| {
+            let mut call_stack = self.call_stack.clone();
+            call_stack.pop();
+            let call_stack = call_stack.iter().map(|x| x - 1).collect();
+            RuntimeError {
+                kind,
+                pc: index,
+                operation: Box::new(self.programms[index].clone()),
+                call_stack,
+            }
+        })
+    }
+
+    fn arithmetic(
+        &mut self,
+        name: &DATA,
+        value: &DATA,
+        ret: &DATA,
+        op: &str,
+        (checked, wrapping): IntOp,
+        float: fn(f64, f64) -> f64,
+    ) -> Result<bool, RuntimeErrorKind> {
+        let value = value.get(self)?;
+        let name = name.get(self)?;
+        //an int mixed with a float is promoted to a float
+        if matches!(name, DATA::Float(_)) || matches!(value, DATA::Float(_)) {
+            let result = float(name.to_f64()?, value.to_f64()?);
+            self.store(ret, DATA::Float(result))?;
+            return Ok(true);
+        }
+        let value = value.to_i64()?;
+        let name = name.to_i64()?;
+        //checked_* only fails with a zero divisor or on overflow
+        let result = match checked(name, value) {
+            Some(result) => result,
+            None if value == 0 => return Err(RuntimeErrorKind::DivisionByZero),
+            None if self.wrapping => wrapping(name, value),
+            None => return Err(RuntimeErrorKind::Overflow(op.to_string())),
+        };
+        self.store(ret, DATA::Number(result))?;
+        Ok(true)
+    }
+
+    //enters the function at label pos with the pending args
+    fn call(&mut self, pos: i64, captures: &[(i64, DATA)]) -> Result<bool, RuntimeErrorKind> {
+        let target = self.label(pos)?;
+        let mut frame = Frame::default();
+        for (i, arg) in self.args.drain(..).enumerate() {
+            frame.locals.insert(i as i64, arg);
+        }
+        for (slot, value) in captures {
+            frame.locals.insert(*slot, value.clone());
+        }
+        self.frames.push(frame);
+        self.result = None;
+        self.call_stack.push(target);
+        Ok(true)
+    }
+
+    fn compare(&mut self, name: &DATA, value: &DATA) -> Result<Option<Ordering>, RuntimeErrorKind> {
+        let value = value.get(self)?;
+        let name = name.get(self)?;
+        match (&name, &value) {
+            (DATA::String(a), DATA::String(b)) => return Ok(Some(a.cmp(b))),
+            (DATA::String(_), _) | (_, DATA::String(_)) => {
+                return Err(RuntimeErrorKind::TypeError(format!(
+                    "cannot compare {} with {}",
+                    name.fancy_string(),
+                    value.fancy_string()
+                )))
+            }
+            _ => {}
+        }
+        if matches!(name, DATA::Float(_)) || matches!(value, DATA::Float(_)) {
+            return Ok(name.to_f64()?.partial_cmp(&value.to_f64()?));
+        }
+        Ok(Some(name.to_i64()?.cmp(&value.to_i64()?)))
+    }
+
+    fn execute(&mut self, index: usize, operation: &Operation) -> Result<bool, RuntimeErrorKind> {
         match operation {
             Operation::DROP { name } => {
-                let pos = name.get(self).to_i32();
-                self.data.remove(&pos);
-                true
+                match name {
+                    DATA::LOCAL(p) => {
+                        self.frames.last_mut().unwrap().locals.remove(p);
+                    }
+                    _ => {
+                        let pos = name.get(self)?.to_i64()?;
+                        self.data.remove(&pos);
+                    }
+                }
+                Ok(true)
             }
             Operation::POINT { name } => {
-                let pos = name.get(self).to_i32();
+                let pos = name.get(self)?.to_i64()?;
                 self.points.insert(pos, index);
-                true
+                Ok(true)
             }
             Operation::SET { name, value } => {
-                let value = value.get(self);
-                let pos = name.get(self).to_i32();
-                self.data.insert(pos, value);
-                true
+                let value = value.get(self)?;
+                self.store(name, value)?;
+                Ok(true)
             }
             Operation::JUMP { name } => {
-                let pos = name.get(self).to_i32();
+                let pos = name.get(self)?.to_i64()?;
+                let target = self.label(pos)?;
                 self.call_stack.pop();
-                self.call_stack.push(self.points.get(&pos).unwrap().clone());
-                true
+                self.call_stack.push(target);
+                Ok(true)
             }
             Operation::PRINT { value } => {
-                println!("{}", value.get(self).to_string());
-                true
+                let line = value.get(self)?.to_string();
+                match &mut self.output {
+                    Some(output) => output.push(line),
+                    None => println!("{}", line),
+                }
+                Ok(true)
+            }
+            Operation::LIST { items, ret } => {
+                let mut values = vec![];
+                for item in items {
+                    values.push(item.get(self)?);
+                }
+                self.store(ret, DATA::list(values))?;
+                Ok(true)
+            }
+            Operation::INDEX { name, index, ret } => {
+                let value = name.get(self)?.index(&index.get(self)?)?;
+                self.store(ret, value)?;
+                Ok(true)
+            }
+            Operation::SET_INDEX { name, index, value } => {
+                let name = name.get(self)?;
+                let index = index.get(self)?;
+                let value = value.get(self)?;
+                name.set_index(&index, value)?;
+                Ok(true)
+            }
+            Operation::MAP { entries, ret } => {
+                let mut map = BTreeMap::new();
+                for (key, value) in entries {
+                    let key = key.get(self)?.to_key()?;
+                    map.insert(key, value.get(self)?);
+                }
+                self.store(ret, DATA::Map(Rc::new(RefCell::new(map))))?;
+                Ok(true)
+            }
+            Operation::RECORD { name, fields, ret } => {
+                let mut values = vec![];
+                for (field, value) in fields {
+                    values.push((field.clone(), value.get(self)?));
+                }
+                let record = Record {
+                    name: name.clone(),
+                    fields: values,
+                };
+                self.store(ret, DATA::Record(Rc::new(RefCell::new(record))))?;
+                Ok(true)
+            }
+            Operation::GET_FIELD { name, field, ret } => {
+                let value = name.get(self)?.field(field)?;
+                self.store(ret, value)?;
+                Ok(true)
+            }
+            Operation::SET_FIELD { name, field, value } => {
+                let value = value.get(self)?;
+                name.get(self)?.set_field(field, value)?;
+                Ok(true)
+            }
+            Operation::BUILTIN { func, args, ret } => {
+                let mut values = vec![];
+                for arg in args {
+                    values.push(arg.get(self)?);
+                }
+                let value = func.call(values)?;
+                self.store(ret, value)?;
+                Ok(true)
             }
             Operation::GREATER { name, value, ret } => {
-                let value = value.get(self).to_i32();
-                let name = name.get(self).to_i32();
-                let ret = ret.get(self).to_i32();
-                self.data.insert(ret, DATA::Bool(name > value));
-                true
+                let result = self.compare(name, value)? == Some(Ordering::Greater);
+                self.store(ret, DATA::Bool(result))?;
+                Ok(true)
+            }
+            Operation::GREATER_EQ { name, value, ret } => {
+                let result = matches!(
+                    self.compare(name, value)?,
+                    Some(Ordering::Greater | Ordering::Equal)
+                );
+                self.store(ret, DATA::Bool(result))?;
+                Ok(true)
             }
             Operation::CALL { name } => {
-                let pos = name.get(self).to_i32();
-                self.call_stack.push(self.points.get(&pos).unwrap().clone());
-                true
+                let pos = name.get(self)?.to_i64()?;
+                self.call(pos, &[])
+            }
+            Operation::CLOSURE {
+                name,
+                arity,
+                captures,
+                self_slot,
+                ret,
+            } => {
+                let mut values = vec![];
+                for (slot, value) in captures {
+                    values.push((*slot, value.get(self)?));
+                }
+                let function = Function {
+                    addr: name.get(self)?.to_i64()?,
+                    arity: *arity,
+                    captures: values,
+                    self_slot: *self_slot,
+                };
+                self.store(ret, DATA::Function(Rc::new(function)))?;
+                Ok(true)
+            }
+            Operation::CALL_VALUE { name } => {
+                let name = name.get(self)?;
+                let DATA::Function(function) = name else {
+                    return Err(RuntimeErrorKind::TypeError(format!(
+                        "cannot call {}",
+                        name.fancy_string()
+                    )));
+                };
+                if self.args.len() != function.arity {
+                    return Err(RuntimeErrorKind::ArityMismatch(
+                        function.arity,
+                        self.args.len(),
+                    ));
+                }
+                self.call(function.addr, &function.captures)?;
+                if let Some(slot) = function.self_slot {
+                    let frame = self.frames.last_mut().unwrap();
+                    frame.locals.insert(slot, DATA::Function(function.clone()));
+                }
+                Ok(true)
+            }
+            Operation::ARG { value } => {
+                let value = value.get(self)?;
+                self.args.push(value);
+                Ok(true)
+            }
+            Operation::RESULT { name } => match self.result.take() {
+                Some(value) => {
+                    self.store(name, value)?;
+                    Ok(true)
+                }
+                None => Err(RuntimeErrorKind::NoReturnValue),
+            },
+            Operation::RETURN { value } => {
+                let value = value.get(self)?;
+                self.ret()?;
+                self.result = Some(value);
+                Ok(true)
             }
             Operation::JUMP_IF { name, condition } => {
-                if condition.get(self).to_i32() == 1 {
-                    let pos = name.get(self).to_i32();
+                if condition.get(self)?.to_i64()? == 1 {
+                    let pos = name.get(self)?.to_i64()?;
+                    let target = self.label(pos)?;
                     self.call_stack.pop();
-                    self.call_stack.push(self.points.get(&pos).unwrap().clone());
+                    self.call_stack.push(target);
                 }
-                true
+                Ok(true)
             }
             Operation::EQ { name, value, ret } => {
-                let value = value.get(self).to_i32();
-                let name = name.get(self).to_i32();
-                let ret = ret.get(self).to_i32();
-                self.data.insert(ret, DATA::Bool(name == value));
-                true
+                let name = name.get(self)?;
+                let value = value.get(self)?;
+                //numbers compare by value, other kinds only equal their own kind,
+                //containers compare by content
+                let result = name.equals(&value, &mut vec![]);
+                self.store(ret, DATA::Bool(result))?;
+                Ok(true)
             }
             Operation::NOT { name, ret } => {
-                let name = name.get(self).to_i32();
-                let ret = ret.get(self).to_i32();
-                self.data.insert(ret, DATA::Bool(name == 0));
-                true
+                let name = name.get(self)?.to_f64()?;
+                self.store(ret, DATA::Bool(name == 0.0))?;
+                Ok(true)
             }
             Operation::ADD { name, value, ret } => {
-                let value = value.get(self).to_i32();
-                let name = name.get(self).to_i32();
-                let ret = ret.get(self).to_i32();
-                self.data.insert(ret, DATA::Number(name + value));
-                true
-            }
-            Operation::SUB { name, value, ret } => {
-                let value = value.get(self).to_i32();
-                let name = name.get(self).to_i32();
-                let ret = ret.get(self).to_i32();
-                self.data.insert(ret, DATA::Number(name - value));
-                true
-            }
-            Operation::MUL { name, value, ret } => {
-                let value = value.get(self).to_i32();
-                let name = name.get(self).to_i32();
-                let ret = ret.get(self).to_i32();
-                self.data.insert(ret, DATA::Number(name * value));
-                true
-            }
-            Operation::DIV { name, value, ret } => {
-                let value = value.get(self).to_i32();
-                let name = name.get(self).to_i32();
-                let ret = ret.get(self).to_i32();
-                self.data.insert(ret, DATA::Number(name / value));
-                true
+                let name = name.get(self)?;
+                let value = value.get(self)?;
+                //a string on either side turns + into concatenation
+                if matches!(name, DATA::String(_)) || matches!(value, DATA::String(_)) {
+                    let result = name.to_string() + &value.to_string();
+                    self.store(ret, DATA::String(result))?;
+                    return Ok(true);
+                }
+                self.arithmetic(
+                    &name,
+                    &value,
+                    ret,
+                    "add",
+                    (i64::checked_add, i64::wrapping_add),
+                    |a, b| a + b,
+                )
             }
+            Operation::SUB { name, value, ret } => self.arithmetic(
+                name,
+                value,
+                ret,
+                "sub",
+                (i64::checked_sub, i64::wrapping_sub),
+                |a, b| a - b,
+            ),
+            Operation::MUL { name, value, ret } => self.arithmetic(
+                name,
+                value,
+                ret,
+                "mul",
+                (i64::checked_mul, i64::wrapping_mul),
+                |a, b| a * b,
+            ),
+            Operation::DIV { name, value, ret } => self.arithmetic(
+                name,
+                value,
+                ret,
+                "div",
+                (i64::checked_div, i64::wrapping_div),
+                |a, b| a / b,
+            ),
+            Operation::MOD { name, value, ret } => self.arithmetic(
+                name,
+                value,
+                ret,
+                "mod",
+                (i64::checked_rem, i64::wrapping_rem),
+                |a, b| a % b,
+            ),
             Operation::RET => {
-                self.call_stack.pop();
-                true
+                self.ret()?;
+                Ok(true)
             }
-            Operation::HALT => false,
-            Operation::NOP => true,
+            Operation::HALT => Ok(false),
+            Operation::NOP => Ok(true),
         }
     }
 }
 
 pub mod easy {
+    use super::Builtin;
     use super::Operation;
     use super::DATA;
     pub fn set(value: String, name: String) -> Operation {
@@ -295,6 +1032,21 @@ pub mod easy {
             name: DATA::from_string(name.to_string()),
         }
     }
+    pub fn arg(value: String) -> Operation {
+        Operation::ARG {
+            value: DATA::from_string(value.to_string()),
+        }
+    }
+    pub fn result(name: String) -> Operation {
+        Operation::RESULT {
+            name: DATA::from_string(name.to_string()),
+        }
+    }
+    pub fn return_value(value: String) -> Operation {
+        Operation::RETURN {
+            value: DATA::from_string(value.to_string()),
+        }
+    }
     pub fn ret() -> Operation {
         Operation::RET
     }
@@ -314,6 +1066,20 @@ pub mod easy {
             ret: DATA::from_string(ret.to_string()),
         }
     }
+    pub fn greater(name: String, value: String, ret: String) -> Operation {
+        Operation::GREATER {
+            name: DATA::from_string(name.to_string()),
+            value: DATA::from_string(value.to_string()),
+            ret: DATA::from_string(ret.to_string()),
+        }
+    }
+    pub fn greater_eq(name: String, value: String, ret: String) -> Operation {
+        Operation::GREATER_EQ {
+            name: DATA::from_string(name.to_string()),
+            value: DATA::from_string(value.to_string()),
+            ret: DATA::from_string(ret.to_string()),
+        }
+    }
     pub fn not(name: String, ret: String) -> Operation {
         Operation::NOT {
             name: DATA::from_string(name.to_string()),
@@ -348,6 +1114,107 @@ pub mod easy {
             ret: DATA::from_string(ret.to_string()),
         }
     }
+    pub fn modulo(name: String, value: String, ret: String) -> Operation {
+        Operation::MOD {
+            name: DATA::from_string(name.to_string()),
+            value: DATA::from_string(value.to_string()),
+            ret: DATA::from_string(ret.to_string()),
+        }
+    }
+    pub fn list(items: Vec<String>, ret: String) -> Operation {
+        Operation::LIST {
+            items: items.into_iter().map(DATA::from_string).collect(),
+            ret: DATA::from_string(ret),
+        }
+    }
+    pub fn index(name: String, index: String, ret: String) -> Operation {
+        Operation::INDEX {
+            name: DATA::from_string(name),
+            index: DATA::from_string(index),
+            ret: DATA::from_string(ret),
+        }
+    }
+    pub fn set_index(name: String, index: String, value: String) -> Operation {
+        Operation::SET_INDEX {
+            name: DATA::from_string(name),
+            index: DATA::from_string(index),
+            value: DATA::from_string(value),
+        }
+    }
+    pub fn map(entries: Vec<String>, ret: String) -> Operation {
+        Operation::MAP {
+            entries: entries
+                .chunks(2)
+                .map(|kv| {
+                    (
+                        DATA::from_string(kv[0].clone()),
+                        DATA::from_string(kv[1].clone()),
+                    )
+                })
+                .collect(),
+            ret: DATA::from_string(ret),
+        }
+    }
+    pub fn record(name: String, fields: Vec<String>, ret: String) -> Operation {
+        Operation::RECORD {
+            name,
+            fields: fields
+                .chunks(2)
+                .map(|fv| (fv[0].clone(), DATA::from_string(fv[1].clone())))
+                .collect(),
+            ret: DATA::from_string(ret),
+        }
+    }
+    pub fn get_field(name: String, field: String, ret: String) -> Operation {
+        Operation::GET_FIELD {
+            name: DATA::from_string(name),
+            field,
+            ret: DATA::from_string(ret),
+        }
+    }
+    pub fn set_field(name: String, field: String, value: String) -> Operation {
+        Operation::SET_FIELD {
+            name: DATA::from_string(name),
+            field,
+            value: DATA::from_string(value),
+        }
+    }
+    pub fn closure(
+        name: String,
+        arity: String,
+        self_slot: String,
+        captures: Vec<String>,
+        ret: String,
+    ) -> Operation {
+        Operation::CLOSURE {
+            name: DATA::from_string(name),
+            arity: arity.parse::<usize>().unwrap(),
+            //`-` when the function does not refer to itself
+            self_slot: self_slot.parse::<i64>().ok(),
+            captures: captures
+                .chunks(2)
+                .map(|sv| {
+                    (
+                        sv[0].parse::<i64>().unwrap(),
+                        DATA::from_string(sv[1].clone()),
+                    )
+                })
+                .collect(),
+            ret: DATA::from_string(ret),
+        }
+    }
+    pub fn call_value(name: String) -> Operation {
+        Operation::CALL_VALUE {
+            name: DATA::from_string(name),
+        }
+    }
+    pub fn builtin(func: String, args: Vec<String>, ret: String) -> Operation {
+        Operation::BUILTIN {
+            func: Builtin::from_name(&func).unwrap(),
+            args: args.into_iter().map(DATA::from_string).collect(),
+            ret: DATA::from_string(ret),
+        }
+    }
     pub fn from_string(s: String) -> Vec<Operation> {
         s.split(";")
             .map(|s| {
@@ -370,24 +1237,60 @@ pub mod easy {
                     "jump" => jump(name[1].clone()),
                     "print" => print(name[1].clone()),
                     "call" => call(name[1].clone()),
+                    "arg" => arg(name[1].clone()),
+                    "result" => result(name[1].clone()),
+                    "return" => return_value(name[1].clone()),
                     "ret" => ret(),
                     "halt" => halt(),
                     "jump_if" => jump_if(name[1].clone(), name[2].clone()),
                     "eq" => eq(name[1].clone(), name[2].clone(), name[3].clone()),
+                    "greater" => greater(name[1].clone(), name[2].clone(), name[3].clone()),
+                    "greater_eq" => greater_eq(name[1].clone(), name[2].clone(), name[3].clone()),
                     "not" => not(name[1].clone(), name[2].clone()),
                     "add" => add(name[1].clone(), name[2].clone(), name[3].clone()),
                     "sub" => sub(name[1].clone(), name[2].clone(), name[3].clone()),
                     "mul" => mul(name[1].clone(), name[2].clone(), name[3].clone()),
                     "div" => div(name[1].clone(), name[2].clone(), name[3].clone()),
+                    "mod" => modulo(name[1].clone(), name[2].clone(), name[3].clone()),
+                    "list" => list(
+                        name[1..name.len() - 1].to_vec(),
+                        name[name.len() - 1].clone(),
+                    ),
+                    "index" => index(name[1].clone(), name[2].clone(), name[3].clone()),
+                    "set_index" => set_index(name[1].clone(), name[2].clone(), name[3].clone()),
+                    "map" => map(
+                        name[1..name.len() - 1].to_vec(),
+                        name[name.len() - 1].clone(),
+                    ),
+                    "record" => record(
+                        name[1].clone(),
+                        name[2..name.len() - 1].to_vec(),
+                        name[name.len() - 1].clone(),
+                    ),
+                    "get_field" => get_field(name[1].clone(), name[2].clone(), name[3].clone()),
+                    "set_field" => set_field(name[1].clone(), name[2].clone(), name[3].clone()),
+                    "closure" => closure(
+                        name[1].clone(),
+                        name[2].clone(),
+                        name[3].clone(),
+                        name[4..name.len() - 1].to_vec(),
+                        name[name.len() - 1].clone(),
+                    ),
+                    "call_value" => call_value(name[1].clone()),
+                    "builtin" => builtin(
+                        name[1].clone(),
+                        name[2..name.len() - 1].to_vec(),
+                        name[name.len() - 1].clone(),
+                    ),
                     "" => Operation::NOP,
                     _ => panic!("Unknown Operation: {}", name[0]),
                 }
             })
             .collect()
     }
-    pub fn run(s: String) {
+    pub fn run(s: String) -> Result<(), super::RuntimeError> {
         let operations = from_string(s);
-        super::Interpreter::run(operations);
+        super::Interpreter::run(operations)
     }
     pub fn fancy_string(operations: Vec<Operation>) -> String {
         operations
@@ -396,6 +1299,86 @@ pub mod easy {
                 Operation::DROP { name } => {
                     format!("drop {};", name.fancy_string())
                 }
+                Operation::LIST { items, ret } => {
+                    let mut s = "list".to_string();
+                    for item in items {
+                        s += &format!(" {}", item.fancy_string());
+                    }
+                    format!("{} {};", s, ret.fancy_string())
+                }
+                Operation::INDEX { name, index, ret } => {
+                    format!(
+                        "index {} {} {};",
+                        name.fancy_string(),
+                        index.fancy_string(),
+                        ret.fancy_string()
+                    )
+                }
+                Operation::SET_INDEX { name, index, value } => {
+                    format!(
+                        "set_index {} {} {};",
+                        name.fancy_string(),
+                        index.fancy_string(),
+                        value.fancy_string()
+                    )
+                }
+                Operation::MAP { entries, ret } => {
+                    let mut s = "map".to_string();
+                    for (key, value) in entries {
+                        s += &format!(" {} {}", key.fancy_string(), value.fancy_string());
+                    }
+                    format!("{} {};", s, ret.fancy_string())
+                }
+                Operation::RECORD { name, fields, ret } => {
+                    let mut s = format!("record {}", name);
+                    for (field, value) in fields {
+                        s += &format!(" {} {}", field, value.fancy_string());
+                    }
+                    format!("{} {};", s, ret.fancy_string())
+                }
+                Operation::GET_FIELD { name, field, ret } => {
+                    format!(
+                        "get_field {} {} {};",
+                        name.fancy_string(),
+                        field,
+                        ret.fancy_string()
+                    )
+                }
+                Operation::SET_FIELD { name, field, value } => {
+                    format!(
+                        "set_field {} {} {};",
+                        name.fancy_string(),
+                        field,
+                        value.fancy_string()
+                    )
+                }
+                Operation::CLOSURE {
+                    name,
+                    arity,
+                    captures,
+                    self_slot,
+                    ret,
+                } => {
+                    let self_slot = match self_slot {
+                        Some(slot) => slot.to_string(),
+                        None => "-".to_string(),
+                    };
+                    let mut s = format!("closure {} {} {}", name.fancy_string(), arity, self_slot);
+                    for (slot, value) in captures {
+                        s += &format!(" {} {}", slot, value.fancy_string());
+                    }
+                    format!("{} {};", s, ret.fancy_string())
+                }
+                Operation::CALL_VALUE { name } => {
+                    format!("call_value {};", name.fancy_string())
+                }
+                Operation::BUILTIN { func, args, ret } => {
+                    let mut s = format!("builtin {}", func.name());
+                    for arg in args {
+                        s += &format!(" {}", arg.fancy_string());
+                    }
+                    format!("{} {};", s, ret.fancy_string())
+                }
                 Operation::GREATER { name, value, ret } => {
                     format!(
                         "greater {} {} {};",
@@ -404,6 +1387,14 @@ pub mod easy {
                         ret.fancy_string()
                     )
                 }
+                Operation::GREATER_EQ { name, value, ret } => {
+                    format!(
+                        "greater_eq {} {} {};",
+                        name.fancy_string(),
+                        value.fancy_string(),
+                        ret.fancy_string()
+                    )
+                }
                 Operation::HALT => "halt;".to_string(),
                 Operation::ADD { name, value, ret } => {
                     format!(
@@ -437,6 +1428,14 @@ pub mod easy {
                         ret.fancy_string()
                     )
                 }
+                Operation::MOD { name, value, ret } => {
+                    format!(
+                        "mod {} {} {};",
+                        name.fancy_string(),
+                        value.fancy_string(),
+                        ret.fancy_string()
+                    )
+                }
                 Operation::NOT { name, ret } => {
                     format!("not {} {};", name.fancy_string(), ret.fancy_string())
                 }
@@ -470,6 +1469,15 @@ pub mod easy {
                 Operation::CALL { name } => {
                     format!("call {};", name.fancy_string())
                 }
+                Operation::ARG { value } => {
+                    format!("arg {};", value.fancy_string())
+                }
+                Operation::RESULT { name } => {
+                    format!("result {};", name.fancy_string())
+                }
+                Operation::RETURN { value } => {
+                    format!("return {};", value.fancy_string())
+                }
                 Operation::RET => "ret;".to_string(),
                 Operation::NOP => "NOP;".to_string(),
             })
@@ -0,0 +1,470 @@
+use std::fmt;
+
+use crate::parser::ParseError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Pos {
+    pub line: usize,
+    pub col: usize,
+}
+
+impl fmt::Display for Pos {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.col)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    Ident(String),
+    //up to i64::MAX + 1, which is only valid after a minus
+    Number(u64),
+    Float(f64),
+    String(String),
+    //a string literal containing `{expr}`
+    Template(Vec<TemplatePart>),
+    Let,
+    Struct,
+    Print,
+    Def,
+    Fn,
+    Return,
+    While,
+    For,
+    In,
+    Loop,
+    Break,
+    Continue,
+    If,
+    Else,
+    Drop,
+    Global,
+    Halt,
+    Exit,
+    True,
+    False,
+    And,
+    Or,
+    LParen,
+    RParen,
+    LBrace,
+    RBrace,
+    LBracket,
+    RBracket,
+    Comma,
+    Colon,
+    Dot,
+    Semicolon,
+    Assign,
+    EQ,
+    NotEq,
+    Greater,
+    GreaterEq,
+    Less,
+    LessEq,
+    Not,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    EOF,
+}
+
+impl TokenKind {
+    fn keyword(s: &str) -> Option<TokenKind> {
+        match s {
+            "let" => Some(TokenKind::Let),
+            "struct" => Some(TokenKind::Struct),
+            "print" => Some(TokenKind::Print),
+            "def" => Some(TokenKind::Def),
+            "fn" => Some(TokenKind::Fn),
+            "return" => Some(TokenKind::Return),
+            "while" => Some(TokenKind::While),
+            "for" => Some(TokenKind::For),
+            "in" => Some(TokenKind::In),
+            "loop" => Some(TokenKind::Loop),
+            "break" => Some(TokenKind::Break),
+            "continue" => Some(TokenKind::Continue),
+            "if" => Some(TokenKind::If),
+            "else" => Some(TokenKind::Else),
+            "drop" => Some(TokenKind::Drop),
+            "global" => Some(TokenKind::Global),
+            "halt" => Some(TokenKind::Halt),
+            "exit" => Some(TokenKind::Exit),
+            "true" => Some(TokenKind::True),
+            "false" => Some(TokenKind::False),
+            "and" => Some(TokenKind::And),
+            "or" => Some(TokenKind::Or),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TemplatePart {
+    Text(String),
+    //the tokens of the expression, ending with EOF
+    Code(Vec<Token>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub text: String,
+    pub pos: Pos,
+}
+
+struct Lexer {
+    chars: Vec<char>,
+    index: usize,
+    pos: Pos,
+}
+
+impl Lexer {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.index).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.index += 1;
+        if c == '\n' {
+            self.pos.line += 1;
+            self.pos.col = 1;
+        } else {
+            self.pos.col += 1;
+        }
+        Some(c)
+    }
+
+    fn text(&self, start: usize) -> String {
+        self.chars[start..self.index].iter().collect()
+    }
+
+    //skips whitespace, `//` line comments and nested `/* */` block comments
+    fn skip_whitespace(&mut self) -> Result<(), ParseError> {
+        while let Some(c) = self.peek() {
+            let next = self.chars.get(self.index + 1).copied();
+            if c.is_whitespace() {
+                self.bump();
+            } else if c == '/' && next == Some('/') {
+                while let Some(c) = self.bump() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            } else if c == '/' && next == Some('*') {
+                self.block_comment()?;
+            } else {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn block_comment(&mut self) -> Result<(), ParseError> {
+        let pos = self.pos;
+        let start = self.index;
+        let mut depth = 0;
+        loop {
+            match (self.bump(), self.peek()) {
+                (Some('/'), Some('*')) => {
+                    self.bump();
+                    depth += 1;
+                }
+                (Some('*'), Some('/')) => {
+                    self.bump();
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(());
+                    }
+                }
+                (Some(_), _) => {}
+                (None, _) => {
+                    return Err(ParseError::new(
+                        pos,
+                        &self.text(start),
+                        "unterminated block comment".to_string(),
+                    ))
+                }
+            }
+        }
+    }
+
+    fn string(&mut self, pos: Pos, start: usize) -> Result<TokenKind, ParseError> {
+        let mut parts = vec![];
+        let mut s = String::new();
+        loop {
+            match self.bump() {
+                Some('"') => break,
+                Some('\\') => s.push(self.escape()?),
+                Some('{') => {
+                    parts.push(TemplatePart::Text(std::mem::take(&mut s)));
+                    parts.push(TemplatePart::Code(self.interpolation()?));
+                }
+                Some(c) => s.push(c),
+                None => {
+                    return Err(ParseError::new(
+                        pos,
+                        &self.text(start),
+                        "unterminated string literal".to_string(),
+                    ))
+                }
+            }
+        }
+        if parts.is_empty() {
+            return Ok(TokenKind::String(s));
+        }
+        parts.push(TemplatePart::Text(s));
+        Ok(TokenKind::Template(parts))
+    }
+
+    //called after the backslash
+    fn escape(&mut self) -> Result<char, ParseError> {
+        let pos = Pos {
+            line: self.pos.line,
+            col: self.pos.col - 1,
+        };
+        let start = self.index - 1;
+        let c = match self.bump() {
+            Some('"') => '"',
+            Some('\\') => '\\',
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some('0') => '\0',
+            Some('{') => '{',
+            Some('}') => '}',
+            Some('u') => {
+                if self.bump() != Some('{') {
+                    return Err(ParseError::new(
+                        pos,
+                        &self.text(start),
+                        "expected `{` after `\\u`".to_string(),
+                    ));
+                }
+                let mut hex = String::new();
+                loop {
+                    match self.bump() {
+                        Some('}') => break,
+                        Some(c) if c.is_ascii_hexdigit() => hex.push(c),
+                        None | Some('"') => {
+                            return Err(ParseError::new(
+                                pos,
+                                &self.text(start),
+                                "unterminated unicode escape".to_string(),
+                            ))
+                        }
+                        Some(_) => {
+                            return Err(ParseError::new(
+                                pos,
+                                &self.text(start),
+                                format!("invalid unicode escape `{}`", self.text(start)),
+                            ))
+                        }
+                    }
+                }
+                match u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                    Some(c) => c,
+                    None => {
+                        return Err(ParseError::new(
+                            pos,
+                            &self.text(start),
+                            format!("invalid unicode escape `{}`", self.text(start)),
+                        ))
+                    }
+                }
+            }
+            _ => {
+                return Err(ParseError::new(
+                    pos,
+                    &self.text(start),
+                    format!("unknown escape `{}`", self.text(start)),
+                ))
+            }
+        };
+        Ok(c)
+    }
+
+    //lexes the tokens of `{expr}` up to the matching `}`
+    fn interpolation(&mut self) -> Result<Vec<Token>, ParseError> {
+        let mut tokens = vec![];
+        let mut depth = 0;
+        loop {
+            let token = self.next_token()?;
+            match token.kind {
+                TokenKind::LBrace => depth += 1,
+                TokenKind::RBrace if depth == 0 && tokens.is_empty() => {
+                    return Err(ParseError::new(
+                        token.pos,
+                        &token.text,
+                        "empty interpolation in string literal".to_string(),
+                    ))
+                }
+                TokenKind::RBrace if depth == 0 => {
+                    tokens.push(Token {
+                        kind: TokenKind::EOF,
+                        text: String::new(),
+                        pos: token.pos,
+                    });
+                    return Ok(tokens);
+                }
+                TokenKind::RBrace => depth -= 1,
+                TokenKind::EOF => {
+                    return Err(ParseError::new(
+                        token.pos,
+                        "",
+                        "unterminated interpolation in string literal".to_string(),
+                    ))
+                }
+                _ => {}
+            }
+            tokens.push(token);
+        }
+    }
+
+    fn next_token(&mut self) -> Result<Token, ParseError> {
+        self.skip_whitespace()?;
+        let pos = self.pos;
+        let start = self.index;
+        let c = match self.bump() {
+            Some(c) => c,
+            None => {
+                return Ok(Token {
+                    kind: TokenKind::EOF,
+                    text: String::new(),
+                    pos,
+                })
+            }
+        };
+        let kind = match c {
+            '(' => TokenKind::LParen,
+            ')' => TokenKind::RParen,
+            '{' => TokenKind::LBrace,
+            '}' => TokenKind::RBrace,
+            '[' => TokenKind::LBracket,
+            ']' => TokenKind::RBracket,
+            ',' => TokenKind::Comma,
+            ':' => TokenKind::Colon,
+            '.' => TokenKind::Dot,
+            ';' => TokenKind::Semicolon,
+            '+' => TokenKind::Plus,
+            '-' => TokenKind::Minus,
+            '*' => TokenKind::Star,
+            '/' => TokenKind::Slash,
+            '%' => TokenKind::Percent,
+            '>' => {
+                if self.peek() == Some('=') {
+                    self.bump();
+                    TokenKind::GreaterEq
+                } else {
+                    TokenKind::Greater
+                }
+            }
+            '<' => {
+                if self.peek() == Some('=') {
+                    self.bump();
+                    TokenKind::LessEq
+                } else {
+                    TokenKind::Less
+                }
+            }
+            '!' => {
+                if self.peek() == Some('=') {
+                    self.bump();
+                    TokenKind::NotEq
+                } else {
+                    TokenKind::Not
+                }
+            }
+            '=' => {
+                if self.peek() == Some('=') {
+                    self.bump();
+                    TokenKind::EQ
+                } else {
+                    TokenKind::Assign
+                }
+            }
+            '"' => self.string(pos, start)?,
+            c if c.is_ascii_digit() => {
+                while let Some(c) = self.peek() {
+                    if !c.is_ascii_digit() {
+                        break;
+                    }
+                    self.bump();
+                }
+                //a `.` only starts a fraction when a digit follows it
+                let fraction = self.peek() == Some('.')
+                    && self
+                        .chars
+                        .get(self.index + 1)
+                        .is_some_and(|c| c.is_ascii_digit());
+                if fraction {
+                    self.bump();
+                    while let Some(c) = self.peek() {
+                        if !c.is_ascii_digit() {
+                            break;
+                        }
+                        self.bump();
+                    }
+                }
+                let text = self.text(start);
+                if fraction {
+                    TokenKind::Float(text.parse::<f64>().unwrap())
+                } else {
+                    match text.parse::<u64>() {
+                        Ok(n) if n <= i64::MAX as u64 + 1 => TokenKind::Number(n),
+                        _ => {
+                            return Err(ParseError::new(
+                                pos,
+                                &text,
+                                format!("number out of range: {}", text),
+                            ))
+                        }
+                    }
+                }
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                while let Some(c) = self.peek() {
+                    if !c.is_alphanumeric() && c != '_' {
+                        break;
+                    }
+                    self.bump();
+                }
+                let text = self.text(start);
+                TokenKind::keyword(&text).unwrap_or(TokenKind::Ident(text))
+            }
+            c => {
+                return Err(ParseError::new(
+                    pos,
+                    &c.to_string(),
+                    format!("unexpected character `{}`", c),
+                ))
+            }
+        };
+        Ok(Token {
+            kind,
+            text: self.text(start),
+            pos,
+        })
+    }
+}
+
+pub fn tokenize(s: &str) -> Result<Vec<Token>, ParseError> {
+    let mut lexer = Lexer {
+        chars: s.chars().collect(),
+        index: 0,
+        pos: Pos { line: 1, col: 1 },
+    };
+    let mut tokens = vec![];
+    loop {
+        let token = lexer.next_token()?;
+        let end = token.kind == TokenKind::EOF;
+        tokens.push(token);
+        if end {
+            break;
+        }
+    }
+    Ok(tokens)
+}
@@ -1,11 +1,53 @@
 #![feature(let_chains)]
 #![allow(non_camel_case_types)]
-use std::io::Read;
+use std::fmt;
+pub mod builtin;
 pub mod compiler;
 pub mod interpreter;
+pub mod lexer;
 pub mod parser;
 pub mod timer;
 
+#[derive(Debug, Clone)]
+pub enum Error {
+    Parse(parser::ParseError),
+    Compile(compiler::CompileError),
+    Runtime(interpreter::RuntimeError),
+    //the source file could not be read
+    Io { path: String, message: String },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Parse(e) => e.fmt(f),
+            Error::Compile(e) => e.fmt(f),
+            Error::Runtime(e) => e.fmt(f),
+            Error::Io { path, message } => write!(f, "cannot read `{}`: {}", path, message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<parser::ParseError> for Error {
+    fn from(e: parser::ParseError) -> Error {
+        Error::Parse(e)
+    }
+}
+
+impl From<compiler::CompileError> for Error {
+    fn from(e: compiler::CompileError) -> Error {
+        Error::Compile(e)
+    }
+}
+
+impl From<interpreter::RuntimeError> for Error {
+    fn from(e: interpreter::RuntimeError) -> Error {
+        Error::Runtime(e)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Lang {
     pub compiler: compiler::Compiler,
@@ -22,24 +64,717 @@ impl Lang {
         }
     }
 
-    pub fn run(file_name: String) {
-        let mut f = std::fs::File::open(file_name).unwrap();
-        let mut s = String::new();
-        f.read_to_string(&mut s).unwrap();
+    pub fn run(file_name: String) -> Result<(), Error> {
+        let s = std::fs::read_to_string(&file_name).map_err(|e| Error::Io {
+            path: file_name.clone(),
+            message: e.to_string(),
+        })?;
         let mut lang = Lang::new();
-        lang.continues(s);
+        lang.continues(s)
     }
 
-    pub fn continues(&mut self, s: String) {
-        let s = parser::parse(s);
+    pub fn continues(&mut self, s: String) -> Result<(), Error> {
+        let s = parser::parse(s)?;
         if self.debug {
             println!("====================");
         }
-        let s = self.compiler.continues_compile(s);
+        let s = self.compiler.continues_compile(s)?;
         if self.debug {
             println!("{}", interpreter::easy::fancy_string(s.clone()));
             println!("====================");
         }
-        self.interpreter.continues_run(s);
+        self.interpreter.continues_run(s)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //runs a program and returns the lines it printed
+    fn run(s: &str) -> Result<Vec<String>, Error> {
+        let mut lang = Lang::new();
+        lang.interpreter.output = Some(vec![]);
+        lang.continues(s.to_string())?;
+        Ok(lang.interpreter.output.unwrap())
+    }
+
+    fn output(s: &str) -> Vec<String> {
+        run(s).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    fn error(s: &str) -> String {
+        match run(s) {
+            Ok(lines) => panic!("expected an error, printed {:?}", lines),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    fn parse_error(s: &str) -> parser::ParseError {
+        match parser::parse(s.to_string()) {
+            Ok(instructions) => panic!("expected a parse error, got {:?}", instructions),
+            Err(e) => e,
+        }
+    }
+
+    fn at(line: usize, col: usize) -> lexer::Pos {
+        lexer::Pos { line, col }
+    }
+
+    #[test]
+    fn parse_errors_carry_position_snippet_and_message() {
+        assert_eq!(
+            parse_error("let x = 1\nprint x;"),
+            parser::ParseError::new(at(2, 1), "print", "expected `;`, found `print`".to_string())
+        );
+        assert_eq!(
+            parse_error("print \"abc;"),
+            parser::ParseError::new(
+                at(1, 7),
+                "\"abc;",
+                "unterminated string literal".to_string()
+            )
+        );
+        assert_eq!(
+            parse_error("let x = 1 # 2;"),
+            parser::ParseError::new(at(1, 11), "#", "unexpected character `#`".to_string())
+        );
+        assert_eq!(
+            parse_error("let a = 1;\nwhile a < 3 {\n    a = a + 1;\n    print a\n    print 2;\n}"),
+            parser::ParseError::new(at(5, 5), "print", "expected `;`, found `print`".to_string())
+        );
+        let e = parse_error("if true {\n  print 1;\n");
+        assert_eq!(e.pos, at(3, 1));
+        assert_eq!(e.message, "expected `}`, found end of input");
+    }
+
+    #[test]
+    fn running_a_missing_file_is_an_error() {
+        match Lang::run("/nonexistent/missing.lang".to_string()) {
+            Err(Error::Io { path, .. }) => assert_eq!(path, "/nonexistent/missing.lang"),
+            other => panic!("expected an io error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn compile_errors_point_at_the_offending_name() {
+        assert_eq!(
+            error("let x = 1 +\n  y;"),
+            "compile error at 2:3: variable `y` is not defined"
+        );
+        assert_eq!(
+            error("print [1,\n    Nope { a: 1 }];"),
+            "compile error at 2:5: struct `Nope` is not defined"
+        );
+        assert_eq!(
+            error("print 1;\nprint g();"),
+            "compile error at 2:7: function `g` is not defined"
+        );
+    }
+
+    #[test]
+    fn recursion_keeps_the_callers_locals() {
+        let lines = output(
+            "def fact(n) {
+                if n == 0 { return 1; }
+                let rest = fact(n - 1);
+                return n * rest;
+            }
+            print fact(10);
+            def fib(n) {
+                if n < 2 { return n; }
+                return fib(n - 1) + fib(n - 2);
+            }
+            print fib(15);",
+        );
+        assert_eq!(lines, ["3628800", "610"]);
+    }
+
+    #[test]
+    fn mutually_recursive_functions() {
+        let lines = output(
+            "def even(n) { if n == 0 { return true; } return odd(n - 1); }
+            def odd(n) { if n == 0 { return false; } return even(n - 1); }
+            print even(10);
+            print odd(7);",
+        );
+        assert_eq!(lines, ["true", "true"]);
+    }
+
+    #[test]
+    fn arguments_do_not_leak_into_globals() {
+        let lines = output(
+            "let n = 5;
+            def f(n) { return n + 1; }
+            print f(1);
+            print n;",
+        );
+        assert_eq!(lines, ["2", "5"]);
+    }
+
+    #[test]
+    fn using_the_result_of_a_function_without_return() {
+        let e = error("def f() { let x = 1; } let y = f();");
+        assert!(e.contains("function did not return a value"), "{}", e);
+    }
+
+    #[test]
+    fn else_if_chains_take_the_first_true_branch() {
+        let lines = output(
+            "def grade(n) {
+                if n >= 90 { return \"a\"; }
+                else if n >= 80 { return \"b\"; }
+                else if n >= 70 { return \"c\"; }
+                else { return \"f\"; }
+            }
+            print grade(95); print grade(85); print grade(75); print grade(10);
+            if false { print 1; } else { print 2; }
+            if true { print 3; } else { print 4; }
+            if false { print 5; }
+            print 6;",
+        );
+        assert_eq!(lines, ["a", "b", "c", "f", "2", "3", "6"]);
+    }
+
+    #[test]
+    fn exit_and_halt_stop_the_program_inside_functions() {
+        let lines = output(
+            "def f() { print 1; exit; print 2; }
+            f();
+            print \"after\";",
+        );
+        assert_eq!(lines, ["1"]);
+        let lines = output("def g() { halt; } g(); print 3;");
+        assert!(lines.is_empty());
+    }
+
+    #[test]
+    fn block_variables_shadow_and_go_out_of_scope() {
+        let lines = output(
+            "let x = 1;
+            {
+                let x = x + 10;
+                print x;
+                if true { let x = 100; print x; }
+                print x;
+            }
+            print x;",
+        );
+        assert_eq!(lines, ["11", "100", "11", "1"]);
+        let e = error("{ let y = 1; } print y;");
+        assert!(e.contains("variable `y` is not defined"), "{}", e);
+    }
+
+    #[test]
+    fn leaving_a_block_drops_its_variables() {
+        let ops = compiler::Compiler::compile(
+            parser::parse(
+                "{ let a = 1; let b = [a]; }
+                let i = 0;
+                while i < 3 {
+                    let t = i * 2;
+                    i = i + 1;
+                    if t == 2 { let u = t; continue; }
+                    if t == 4 { let v = t; break; }
+                }"
+                .to_string(),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let dropped: Vec<i64> = ops
+            .iter()
+            .filter_map(|op| match op {
+                interpreter::Operation::DROP {
+                    name: interpreter::DATA::Number(addr),
+                } => Some(*addr),
+                _ => None,
+            })
+            .collect();
+        //a and b, t at the end of the body, u and t on continue, v and t on break,
+        //u and v at the end of their if bodies
+        assert_eq!(dropped.len(), 9);
+        let mut interpreter = interpreter::Interpreter::new();
+        interpreter.continues_run(ops).unwrap();
+        for addr in dropped {
+            assert!(
+                !interpreter.data.contains_key(&addr),
+                "{} was not dropped",
+                addr
+            );
+        }
+    }
+
+    #[test]
+    fn and_or_short_circuit() {
+        let lines = output(
+            "def loud(x) { print x; return x; }
+            print false and loud(1);
+            print true or loud(2);
+            print true and loud(3);
+            print 1 < 2 and 2 <= 2 and 3 >= 4 or 1 != 2;",
+        );
+        assert_eq!(lines, ["false", "true", "3", "true", "true"]);
+    }
+
+    #[test]
+    fn integer_overflow_is_checked() {
+        assert_eq!(
+            output("print 9223372036854775807; print -9223372036854775808;"),
+            ["9223372036854775807", "-9223372036854775808"]
+        );
+        let e = error("let x = 9223372036854775807; print x + 1;");
+        assert!(e.contains("integer overflow in add"), "{}", e);
+        let e = error("print 4611686018427387904 * 2;");
+        assert!(e.contains("integer overflow in mul"), "{}", e);
+        let e = error("print 1 / 0;");
+        assert!(e.contains("division by zero"), "{}", e);
+        let e = error("print -9223372036854775808 - 1;");
+        assert!(e.contains("integer overflow in sub"), "{}", e);
+    }
+
+    #[test]
+    fn wrapping_mode_wraps_on_overflow() {
+        let mut lang = Lang::new();
+        lang.interpreter.output = Some(vec![]);
+        lang.interpreter.wrapping = true;
+        lang.continues("print 9223372036854775807 + 1;".to_string())
+            .unwrap();
+        assert_eq!(lang.interpreter.output.unwrap(), ["-9223372036854775808"]);
+    }
+
+    #[test]
+    fn containers_holding_themselves_print_and_compare() {
+        let lines = output(
+            "let xs = [1]; push(xs, xs); print xs;
+            let ys = [1]; push(ys, ys);
+            print xs == ys; print xs == xs; print xs == [1, [1]];
+            let m = {\"a\": 1}; m[\"self\"] = m; print m;",
+        );
+        assert_eq!(
+            lines,
+            [
+                "[1, ...]",
+                "true",
+                "true",
+                "false",
+                "{\"a\": 1, \"self\": ...}"
+            ]
+        );
+    }
+
+    #[test]
+    fn lambdas_copy_locals_and_read_globals_live() {
+        let lines = output(
+            "def make_adder(n) { return fn(x) { return x + n; }; }
+            let add5 = make_adder(5);
+            print add5(10);
+            print make_adder(1)(2);
+            def apply(f, x) { return f(x); }
+            print apply(add5, 1);
+            {
+                let x = 1;
+                let f = fn() { return x; };
+                x = 2;
+                print f();
+            }
+            let g = 1;
+            let h = fn() { return g; };
+            g = 2;
+            print h();",
+        );
+        assert_eq!(lines, ["15", "3", "6", "1", "2"]);
+    }
+
+    #[test]
+    fn lambdas_kept_in_locals_call_themselves() {
+        let lines = output(
+            "def g() {
+                let f = fn(n) { if n == 0 { return 0; } return n + f(n - 1); };
+                let h = f;
+                let f = 0;
+                return h(3);
+            }
+            print g();
+            def fib(k) {
+                let fib = fn(n) { if n < 2 { return n; } let a = fn() { return fib(n - 1); }; return a() + fib(n - 2); };
+                return fib(k);
+            }
+            print fib(10);
+            {
+                let f = fn(n) { if n == 0 { return 1; } return n * f(n - 1); };
+                print f(5);
+            }
+            def k() { let f = fn(f) { return f; }; return f(7); }
+            print k();",
+        );
+        assert_eq!(lines, ["6", "55", "120", "7"]);
+    }
+
+    #[test]
+    fn calling_a_function_value_checks_its_arity() {
+        let e = error("let f = fn(a, b) { return a; }; print f(1);");
+        assert!(e.contains("takes 2 argument(s) but 1 were given"), "{}", e);
+        let e = error("let n = 1; print n(2);");
+        assert!(e.contains("cannot call 1"), "{}", e);
+    }
+
+    #[test]
+    fn defs_are_hoisted_within_their_function() {
+        let lines = output(
+            "print a();
+            def a() {
+                return helper() + later();
+                def later() { return 10; }
+                def helper() { return 1; }
+            }
+            def b() {
+                def helper() { return 2; }
+                return helper();
+            }
+            print b();
+            if true { print c(); def c() { return 3; } }",
+        );
+        assert_eq!(lines, ["11", "2", "3"]);
+        let e = error("def a() { def helper() { return 1; } return 0; } print helper();");
+        assert!(e.contains("function `helper` is not defined"), "{}", e);
+        let e = error("def f() {}\ndef f(x) {}");
+        assert!(
+            e.contains("at 2:1: function `f` is already defined at 1:1"),
+            "{}",
+            e
+        );
+    }
+
+    #[test]
+    fn arity_mismatch_reports_call_and_definition_site() {
+        let e = error(
+            "def f(x) { return x; }
+let y = 1 +
+    f(1, 2);",
+        );
+        assert_eq!(
+            e,
+            "compile error at 3:5: function `f` takes 1 argument(s) but 2 were given \
+             (defined at 1:1)"
+        );
+        let e = error("let g = fn() { return 1; };\nprint g() + len(1, 2);");
+        assert_eq!(
+            e,
+            "compile error at 2:13: function `len` takes 1 argument(s) but 2 were given"
+        );
+    }
+
+    #[test]
+    fn functions_read_globals_and_assign_declared_ones() {
+        let lines = output(
+            "def bump() { global count; count = count + step(); }
+            def step() { return limit; }
+            let count = 0;
+            let limit = 3;
+            bump();
+            bump();
+            print count;
+            def shadow() { let count = 100; count = count + 1; return count; }
+            print shadow();
+            print count;",
+        );
+        assert_eq!(lines, ["6", "101", "6"]);
+        let e = error("print x; let x = 1;");
+        assert!(e.contains("variable `x` is not defined"), "{}", e);
+    }
+
+    #[test]
+    fn functions_cannot_change_undeclared_globals_or_captures() {
+        let e = error("let g = 1; def f() { g = 2; }");
+        assert!(
+            e.contains("cannot change global `g` without `global g` in the function"),
+            "{}",
+            e
+        );
+        let e = error("let g = 1; def f() { global g; drop g; } print g;");
+        assert!(
+            e.contains("cannot drop global `g` inside a function"),
+            "{}",
+            e
+        );
+        let e = error("{ let n = 1; let f = fn() { n = 2; }; }");
+        assert!(e.contains("cannot assign to `n`"), "{}", e);
+        let e = error("let g = 1; global g;");
+        assert!(
+            e.contains("`global` is only allowed inside a function"),
+            "{}",
+            e
+        );
+        let e = error("def f() { global nope; }");
+        assert!(e.contains("variable `nope` is not defined"), "{}", e);
+    }
+
+    #[test]
+    fn negative_literals_reach_the_minimum_integer() {
+        assert_eq!(
+            output("print -9223372036854775808;"),
+            ["-9223372036854775808"]
+        );
+        assert_eq!(
+            output("print 1 -9223372036854775807;"),
+            ["-9223372036854775806"]
+        );
+        assert_eq!(
+            error("print 9223372036854775808;"),
+            "parse error at 1:7: number out of range: 9223372036854775808"
+        );
+        assert_eq!(
+            error("print -9223372036854775809;"),
+            "parse error at 1:8: number out of range: 9223372036854775809"
+        );
+    }
+
+    #[test]
+    fn floats_promote_ints_and_print_their_fraction() {
+        assert_eq!(
+            output("print 2.0; print -1.25; print 4.0 / 2;"),
+            ["2.0", "-1.25", "2.0"]
+        );
+        assert_eq!(
+            output("print 1 + 2.5; print 3 - 0.5; print 2 * 1.5; print 7 / 2.0; print 7 / 2;"),
+            ["3.5", "2.5", "3.0", "3.5", "3"]
+        );
+        assert_eq!(
+            output("print 1 < 1.5; print 2 == 2.0; print 2.5 >= 3; print 1.0 / 0.0;"),
+            ["true", "true", "false", "inf"]
+        );
+    }
+
+    #[test]
+    fn strings_concatenate_compare_and_use_builtins() {
+        assert_eq!(
+            output(r#"print "ab" + "cd"; print "a" + 1; print "a" < "b";"#),
+            ["abcd", "a1", "true"]
+        );
+        let e = error(r#"print "a" < 1;"#);
+        assert!(
+            e.contains(r#"type error: cannot compare 1 with "a""#),
+            "{}",
+            e
+        );
+        assert_eq!(
+            output(
+                r#"print len("héllo"); print substring("hello", 1, 3); print char_at("hello", 4);
+                print find("hello", "ll"); print find("hello", "z");"#
+            ),
+            ["5", "el", "o", "2", "-1"]
+        );
+        assert_eq!(
+            output(r#"print split("a,b,,c", ","); print len(split("abc", ","));"#),
+            [r#"["a", "b", "", "c"]"#, "1"]
+        );
+        let e = error(r#"print split("a", "");"#);
+        assert!(e.contains("split separator is empty"), "{}", e);
+        let e = error(r#"print char_at("abc", 3);"#);
+        assert!(e.contains("index 3 out of bounds"), "{}", e);
+    }
+
+    #[test]
+    fn string_escapes_and_interpolation() {
+        assert_eq!(
+            output(r#"print "a\"b\\c\td\u{41}\u{e9}"; print "x\ny"; print "\{x\}";"#),
+            ["a\"b\\c\tdAé", "x\ny", "{x}"]
+        );
+        assert_eq!(
+            error(r#"print "\q";"#),
+            "parse error at 1:8: unknown escape `\\q`"
+        );
+        assert_eq!(
+            error(r#"print "\u{zz}";"#),
+            "parse error at 1:8: invalid unicode escape `\\u{z`"
+        );
+        assert_eq!(
+            error(r#"print "\u{41"#),
+            "parse error at 1:8: unterminated unicode escape"
+        );
+        assert_eq!(
+            error(r#"print "\u{110000}";"#),
+            "parse error at 1:8: invalid unicode escape `\\u{110000}`"
+        );
+        assert_eq!(
+            output(r#"let x = 2; print "v={x + 1} s={"in{x}"} m={ {"k": x}["k"] }";"#),
+            ["v=3 s=in2 m=2"]
+        );
+        assert_eq!(
+            error(r#"print "a{}b";"#),
+            "parse error at 1:10: empty interpolation in string literal"
+        );
+        assert_eq!(
+            error(r#"print "a{1";"#),
+            "parse error at 1:11: unterminated string literal"
+        );
+    }
+
+    #[test]
+    fn comments_nest_and_are_ignored_in_strings() {
+        assert_eq!(
+            output(
+                "print 1; // two\n/* a /* nested */ still comment */ print 2;\n\
+                 print \"// not /* a comment\"; print 3 /* inline */ + 1; // end"
+            ),
+            ["1", "2", "// not /* a comment", "4"]
+        );
+        assert_eq!(
+            error("print 1;\n  /* open /* inner */\nprint 2;"),
+            "parse error at 2:3: unterminated block comment"
+        );
+    }
+
+    #[test]
+    fn container_elements_compare_like_scalars() {
+        assert_eq!(
+            output(
+                r#"print [1] == [1.0]; print [true] == [1]; print {"a": [2]} == {"a": [2.0]};
+                print [1] == [2.0]; print [1] == ["1"]; print [1] != [1.0];"#
+            ),
+            ["true", "true", "true", "false", "false", "false"]
+        );
+    }
+
+    #[test]
+    fn maps_get_set_and_use_builtins() {
+        assert_eq!(
+            output(
+                r#"let m = {"a": 1, 2: "b"}; print m; print m["a"]; print m[2];
+                m["c"] = 4; m["a"] = 5; print m;
+                print has(m, "c"); print has(m, "z"); print remove(m, "c");
+                print keys(m); print len(m); print {};"#
+            ),
+            [
+                r#"{2: "b", "a": 1}"#,
+                "1",
+                "b",
+                r#"{2: "b", "a": 5, "c": 4}"#,
+                "true",
+                "false",
+                "4",
+                r#"[2, "a"]"#,
+                "2",
+                "{}"
+            ]
+        );
+        let e = error(r#"let m = {}; print m["x"];"#);
+        assert!(e.contains(r#"key "x" is not in the map"#), "{}", e);
+        let e = error("let m = {}; print remove(m, 1);");
+        assert!(e.contains("key 1 is not in the map"), "{}", e);
+        let e = error("let m = {1.5: 2};");
+        assert!(
+            e.contains("map keys must be integers or strings, found 1.5"),
+            "{}",
+            e
+        );
+    }
+
+    #[test]
+    fn structs_build_records_and_check_their_fields() {
+        assert_eq!(
+            output(
+                "let p = P { x: 1, y: 2 }; print p; print p.x; p.y = 5; print p.y;
+                print p == P { y: 5, x: 1 }; struct P { x, y }"
+            ),
+            ["P { x: 1, y: 2 }", "1", "5", "true"]
+        );
+        assert_eq!(
+            error("struct P { x } print Q { x: 1 };"),
+            "compile error at 1:22: struct `Q` is not defined"
+        );
+        assert_eq!(
+            error("struct P { x } print P { x: 1, z: 2 };"),
+            "compile error at 1:32: struct `P` has no field `z`"
+        );
+        assert_eq!(
+            error("struct P { x, y } print P { x: 1 };"),
+            "compile error at 1:25: missing field `y` in `P`"
+        );
+        assert_eq!(
+            error("struct P { x } print P { x: 1, x: 2 };"),
+            "compile error at 1:32: field `x` of `P` is given more than once"
+        );
+        let e = error("struct P { x } let p = P { x: 1 }; print p.z;");
+        assert!(e.contains("P has no field `z`"), "{}", e);
+        let e = error("struct P { x } let p = P { x: 1 }; p.z = 1;");
+        assert!(e.contains("P has no field `z`"), "{}", e);
+    }
+
+    #[test]
+    fn a_struct_is_defined_once_per_unit() {
+        assert_eq!(
+            error("struct P { x, y }\nif true { struct P { a } }"),
+            "compile error at 2:11: struct `P` is already defined at 1:1"
+        );
+        //a later unit may redefine it
+        let mut lang = Lang::new();
+        lang.interpreter.output = Some(vec![]);
+        lang.continues("struct P { x }".to_string()).unwrap();
+        lang.continues("struct P { a } print P { a: 1 };".to_string())
+            .unwrap();
+        assert_eq!(lang.interpreter.output.unwrap(), ["P { a: 1 }"]);
+    }
+
+    #[test]
+    fn parameters_are_named_once() {
+        assert_eq!(
+            error("def f(a, b, a) { return a; }"),
+            "parse error at 1:13: duplicate parameter `a`"
+        );
+        assert_eq!(
+            error("let f = fn(x, x) { return x; };"),
+            "parse error at 1:15: duplicate parameter `x`"
+        );
+    }
+
+    #[test]
+    fn expression_statements_discard_their_value() {
+        assert_eq!(
+            output(
+                "let n = 0;
+                def f() { global n; n = n + 1; return n; }
+                def v() { print \"v\"; }
+                1 + f(); v(); f();
+                let xs = [1]; push(xs, 2); 5; \"s\";
+                print xs; print n;"
+            ),
+            ["v", "[1, 2]", "2"]
+        );
+        //only a call whose value is used needs a return
+        let e = error("def v() { } 1 + v();");
+        assert!(e.contains("function did not return a value"), "{}", e);
+        assert_eq!(
+            error("f() = 1;"),
+            "parse error at 1:1: cannot assign to a function call"
+        );
+        assert_eq!(
+            error("1 = 2;"),
+            "parse error at 1:1: invalid assignment target"
+        );
+    }
+
+    #[test]
+    fn a_second_top_level_let_updates_the_global_functions_use() {
+        assert_eq!(
+            output("let x = 1; def f() { return x; } let x = 2; print f();"),
+            ["2"]
+        );
+        assert_eq!(
+            output("def f() { return x; } let x = 1; let x = 2; print f();"),
+            ["2"]
+        );
+        assert_eq!(
+            output("let x = 1; def f() { return x; } drop x; let x = [2]; print f();"),
+            ["[2]"]
+        );
+        let mut lang = Lang::new();
+        lang.interpreter.output = Some(vec![]);
+        lang.continues("let x = 1; def f() { return x; }".to_string())
+            .unwrap();
+        lang.continues("let x = 2; print f();".to_string()).unwrap();
+        assert_eq!(lang.interpreter.output.unwrap(), ["2"]);
     }
 }
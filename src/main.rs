@@ -6,7 +6,10 @@ use std::{fs, io::Write};
 fn main() {
     let args = env::args().collect::<Vec<String>>();
     if args.len() == 2 {
-        Lang::run(args[1].clone());
+        if let Err(e) = Lang::run(args[1].clone()) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
         return;
     }
     if args.len() == 3 {
@@ -16,21 +19,33 @@ fn main() {
             let mut f = fs::File::open(args[2].clone()).unwrap();
             let mut s = String::new();
             f.read_to_string(&mut s).unwrap();
-            lang.continues(s);
+            if let Err(e) = lang.continues(s) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
             return;
         }
         if args[1] == "-c" {
             let mut s = String::new();
             let mut f = fs::File::open(args[2].clone()).unwrap();
             f.read_to_string(&mut s).unwrap();
-            let s = lang::parser::parse(s);
+            let s = lang::parser::parse(s).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            });
             println!("====================");
             println!("{:#?}", s);
-            let s = lang::compiler::Compiler::compile(s);
+            let s = lang::compiler::Compiler::compile(s).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            });
             println!("====================");
             println!("{}", lang::interpreter::easy::fancy_string(s.clone()));
             println!("====================");
-            lang::interpreter::Interpreter::run(s);
+            if let Err(e) = lang::interpreter::Interpreter::run(s) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
             return;
         }
         return;
@@ -112,7 +127,9 @@ fn main() {
             //execute history
             for s in history.iter() {
                 println!("|-> {}", s);
-                lang.continues(s.clone());
+                if let Err(e) = lang.continues(s.clone()) {
+                    println!("{}", e);
+                }
             }
             continue;
         }
@@ -129,7 +146,9 @@ fn main() {
             history.extend(s.clone());
             for s in s.iter() {
                 println!("|-> {}", s);
-                lang.continues(s.clone());
+                if let Err(e) = lang.continues(s.clone()) {
+                    println!("{}", e);
+                }
             }
             continue;
         }
@@ -138,14 +157,34 @@ fn main() {
             println!("let <name> = <value>: create a variable");
             println!("<name> = <value>: set var to values");
             println!("if <condition> {{<code>}}: if condition is true, execute code");
+            println!("if <condition> {{<code>}} else if <condition> {{<code>}} else {{<code>}}: first branch whose condition is true");
             println!("while <condition> {{<code>}}: while condition is true, execute code");
-            println!("def <name> (<args>) {{<code>}}: create a function");
+            println!("for <name> in <list> {{<code>}}: execute code for every item");
+            println!("loop {{<code>}}: execute code forever");
+            println!("break / continue: leave the loop / start its next iteration");
+            println!("halt / exit: stop the program, also from inside a function");
+            println!("{{<code>}}: group code into a block");
+            println!("// <text> and /* <text> */: comments, block comments nest");
+            println!("def <name> (<args>) {{<code>}}: create a function, it can be called before its definition, a def inside a function is private to it");
+            println!("<expr>;: evaluate an expression such as a call and discard its result");
+            println!("global <name>: inside a function, allow assigning to a global; functions may read globals without it, including ones declared later, but never drop them");
+            println!("fn (<args>) {{<code>}}: a function value, block and function variables it uses are copied in when it is created and cannot be assigned, globals are read when it runs, `let f = fn ...` lets it call itself as f");
+            println!("<value>(<args>): call a function value, functions can be stored and passed like any value");
+            println!("struct <name> {{<field>, ...}}: declare a struct, build one with <name> {{<field>: <value>, ...}}");
+            println!("<value>.<field>, <name>.<field> = <value>: read and write struct fields");
+            println!("operators: ! -x * / % + - < <= > >= == != and or, (<expr>) for grouping");
+            println!("strings: + concatenates, == < > compare, \"x is {{x}}\" interpolates, escapes \\\" \\\\ \\n \\t \\u{{..}} \\{{");
+            println!("lists: [<value>, ...], <list>[<index>], <name>[<index>] = <value>");
+            println!("maps: {{<key>: <value>, ...}}, <map>[<key>], <name>[<key>] = <value>, keys are integers or strings");
+            println!("builtins: len(s) substring(s, start, end) char_at(s, i) split(s, sep) find(s, needle) push(list, value) pop(list) has(map, key) remove(map, key) keys(map)");
             continue;
         }
         if !s.ends_with(";") {
             s.push(';');
         }
         history.push(s.clone());
-        lang.continues(s);
+        if let Err(e) = lang.continues(s) {
+            println!("{}", e);
+        }
     }
 }
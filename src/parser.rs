@@ -1,311 +1,602 @@
-use std::{str::Chars, vec};
+use std::fmt;
 
 use crate::compiler::*;
+use crate::lexer::{tokenize, Pos, TemplatePart, Token, TokenKind};
 
-pub fn to_expression(input: String) -> Option<Expression> {
-    if smart_contain(input.clone(), '(') {
-        let pos = input.find('(').unwrap();
-        let name = input[0..pos].to_string();
-        let mut split = smart_split(input[pos + 1..].to_string(), ',').into_iter();
-        let mut args = vec![];
-        while let Some(n) = split.next() {
-            let mut n = n.trim().to_string();
-            if n.len() == 0 {
-                continue;
-            }
-            if n.starts_with("+") || n.starts_with("-") || n.starts_with("*") || n.starts_with("/")
-                || n.starts_with(">") || n.starts_with("==")
-            {
-                n.push_str(&split.next().unwrap());
-                args.push(to_expression(n).unwrap());
-            } else {
-                args.push(to_expression(n).unwrap());
-            }
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub pos: Pos,
+    pub snippet: String,
+    pub message: String,
+}
+
+impl ParseError {
+    pub fn new(pos: Pos, snippet: &str, message: String) -> ParseError {
+        ParseError {
+            pos,
+            snippet: snippet.to_string(),
+            message,
         }
-        return Some(Expression::CALL(name, args));
-    } else if input.starts_with("+") {
-        let split = smart_split(input[1..].to_string(), ',');
-        if split.len() == 2 {
-            let left = to_expression(split[0].clone());
-            let right = to_expression(split[1].clone());
-            if let (Some(left), Some(right)) = (left, right) {
-                return Some(Expression::Add(Box::new(left), Box::new(right)));
-            }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "parse error at {}: {}", self.pos, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+pub struct Parser {
+    tokens: Vec<Token>,
+    index: usize,
+    //false in the head of if, while and for, where `{` starts the body
+    structs: bool,
+}
+
+impl Parser {
+    pub fn new(s: &str) -> Result<Parser, ParseError> {
+        Ok(Parser {
+            tokens: tokenize(s)?,
+            index: 0,
+            structs: true,
+        })
+    }
+
+    fn peek(&self) -> &TokenKind {
+        &self.tokens[self.index].kind
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.index].clone();
+        if token.kind != TokenKind::EOF {
+            self.index += 1;
         }
-    } else if input.starts_with("-") {
-        let split = smart_split(input[1..].to_string(), ',');
-        if split.len() == 2 {
-            let left = to_expression(split[0].clone());
-            let right = to_expression(split[1].clone());
-            if let (Some(left), Some(right)) = (left, right) {
-                return Some(Expression::Sub(Box::new(left), Box::new(right)));
-            }
+        token
+    }
+
+    fn eat(&mut self, kind: &TokenKind) -> bool {
+        if self.peek() == kind {
+            self.next();
+            return true;
         }
-    } else if input.starts_with("*") {
-        let split = smart_split(input[1..].to_string(), ',');
-        if split.len() == 2 {
-            let left = to_expression(split[0].clone());
-            let right = to_expression(split[1].clone());
-            if let (Some(left), Some(right)) = (left, right) {
-                return Some(Expression::Mul(Box::new(left), Box::new(right)));
-            }
+        false
+    }
+
+    fn unexpected(token: &Token, what: &str) -> ParseError {
+        let found = if token.kind == TokenKind::EOF {
+            "end of input".to_string()
+        } else {
+            format!("`{}`", token.text)
+        };
+        ParseError::new(
+            token.pos,
+            &token.text,
+            format!("expected {}, found {}", what, found),
+        )
+    }
+
+    fn expect(&mut self, kind: TokenKind, what: &str) -> Result<Token, ParseError> {
+        let token = self.next();
+        if token.kind != kind {
+            return Err(Parser::unexpected(&token, what));
         }
-    } else if input.starts_with("/") {
-        let split = smart_split(input[1..].to_string(), ',');
-        if split.len() == 2 {
-            let left = to_expression(split[0].clone());
-            let right = to_expression(split[1].clone());
-            if let (Some(left), Some(right)) = (left, right) {
-                return Some(Expression::Div(Box::new(left), Box::new(right)));
+        Ok(token)
+    }
+
+    fn ident(&mut self) -> Result<String, ParseError> {
+        let token = self.next();
+        match token.kind {
+            TokenKind::Ident(name) => Ok(name),
+            _ => Err(Parser::unexpected(&token, "identifier")),
+        }
+    }
+
+    pub fn parse_program(&mut self) -> Result<Vec<Instruction>, ParseError> {
+        let instructions = self.parse_statements()?;
+        let token = self.next();
+        if token.kind != TokenKind::EOF {
+            return Err(Parser::unexpected(&token, "statement"));
+        }
+        Ok(instructions)
+    }
+
+    fn parse_body(&mut self) -> Result<Vec<Instruction>, ParseError> {
+        self.expect(TokenKind::LBrace, "`{`")?;
+        let instructions = self.parse_statements()?;
+        self.expect(TokenKind::RBrace, "`}`")?;
+        Ok(instructions)
+    }
+
+    fn parse_statements(&mut self) -> Result<Vec<Instruction>, ParseError> {
+        let mut instructions = vec![];
+        loop {
+            match self.peek() {
+                TokenKind::EOF | TokenKind::RBrace => break,
+                TokenKind::Semicolon => {
+                    self.next();
+                }
+                TokenKind::LBrace => {
+                    instructions.push(Instruction::BLOCK {
+                        instruction: self.parse_body()?,
+                    });
+                }
+                _ => {
+                    let (instruction, block) = self.parse_statement()?;
+                    instructions.push(instruction);
+                    if !block && !self.eat(&TokenKind::Semicolon) {
+                        match self.peek() {
+                            TokenKind::EOF | TokenKind::RBrace => {}
+                            _ => return Err(Parser::unexpected(&self.next(), "`;`")),
+                        }
+                    }
+                }
             }
         }
-    } else if input.starts_with(">") {
-        let split = smart_split(input[1..].to_string(), ',');
-        if split.len() == 2 {
-            let left = to_expression(split[0].clone());
-            let right = to_expression(split[1].clone());
-            if let (Some(left), Some(right)) = (left, right) {
-                return Some(Expression::GREATER(Box::new(left), Box::new(right)));
+        Ok(instructions)
+    }
+
+    //returns the instruction and whether it ended with a block
+    fn parse_statement(&mut self) -> Result<(Instruction, bool), ParseError> {
+        let token = self.next();
+        let pos = token.pos;
+        match token.kind {
+            TokenKind::Print => Ok((
+                Instruction::PRINT {
+                    value: self.parse_expression()?,
+                    pos,
+                },
+                false,
+            )),
+            TokenKind::Return => Ok((
+                Instruction::RETURN {
+                    value: self.parse_expression()?,
+                    pos,
+                },
+                false,
+            )),
+            TokenKind::Halt => Ok((Instruction::HALT, false)),
+            TokenKind::Exit => Ok((Instruction::EXIT, false)),
+            TokenKind::Loop => Ok((
+                Instruction::LOOP {
+                    instruction: self.parse_body()?,
+                },
+                true,
+            )),
+            TokenKind::Break => Ok((Instruction::BREAK { pos }, false)),
+            TokenKind::Continue => Ok((Instruction::CONTINUE { pos }, false)),
+            TokenKind::Drop => Ok((
+                Instruction::DROP {
+                    name: self.ident()?,
+                    pos,
+                },
+                false,
+            )),
+            TokenKind::Global => Ok((
+                Instruction::GLOBAL {
+                    name: self.ident()?,
+                    pos,
+                },
+                false,
+            )),
+            TokenKind::Let => {
+                let name = self.ident()?;
+                self.expect(TokenKind::Assign, "`=`")?;
+                let value = self.parse_expression()?;
+                Ok((Instruction::SET { name, value, pos }, false))
+            }
+            TokenKind::Def => {
+                let name = self.ident()?;
+                let args = self.parse_params()?;
+                let instruction = self.parse_body()?;
+                Ok((
+                    Instruction::FUNCTION {
+                        name,
+                        args,
+                        instruction,
+                        pos,
+                    },
+                    true,
+                ))
+            }
+            TokenKind::Struct => {
+                let name = self.ident()?;
+                self.expect(TokenKind::LBrace, "`{`")?;
+                let mut fields: Vec<String> = vec![];
+                while !self.eat(&TokenKind::RBrace) {
+                    let token = self.tokens[self.index].clone();
+                    let field = self.ident()?;
+                    if fields.contains(&field) {
+                        return Err(ParseError::new(
+                            token.pos,
+                            &token.text,
+                            format!("duplicate field `{}`", field),
+                        ));
+                    }
+                    fields.push(field);
+                    if !self.eat(&TokenKind::Comma) {
+                        self.expect(TokenKind::RBrace, "`}`")?;
+                        break;
+                    }
+                }
+                Ok((Instruction::STRUCT { name, fields, pos }, true))
+            }
+            TokenKind::While => {
+                let condition = self.parse_condition()?;
+                let instruction = self.parse_body()?;
+                Ok((
+                    Instruction::WHILE {
+                        condition,
+                        instruction,
+                        pos,
+                    },
+                    true,
+                ))
+            }
+            TokenKind::For => {
+                let name = self.ident()?;
+                self.expect(TokenKind::In, "`in`")?;
+                let iterable = self.parse_condition()?;
+                let instruction = self.parse_body()?;
+                Ok((
+                    Instruction::FOR {
+                        name,
+                        iterable,
+                        instruction,
+                        pos,
+                    },
+                    true,
+                ))
+            }
+            TokenKind::If => Ok((self.parse_if(pos)?, true)),
+            _ => {
+                //an assignment or an expression statement such as `f(x);`
+                self.index -= 1;
+                let target = self.parse_expression()?;
+                if !self.eat(&TokenKind::Assign) {
+                    return Ok((Instruction::EXPRESSION { value: target, pos }, false));
+                }
+                let value = self.parse_expression()?;
+                //`xs[i].y = v` assigns to the field y of `xs[i]`
+                let instruction = match target {
+                    Expression::Variable(name, _) => Instruction::ASSIGN { name, value, pos },
+                    Expression::Index(target, index) => Instruction::SET_INDEX {
+                        target: *target,
+                        index: *index,
+                        value,
+                        pos,
+                    },
+                    Expression::Field(target, field) => Instruction::SET_FIELD {
+                        target: *target,
+                        field,
+                        value,
+                        pos,
+                    },
+                    Expression::CALL(..) | Expression::CALL_VALUE(..) => {
+                        return Err(ParseError::new(
+                            pos,
+                            &token.text,
+                            "cannot assign to a function call".to_string(),
+                        ))
+                    }
+                    _ => {
+                        return Err(ParseError::new(
+                            pos,
+                            &token.text,
+                            "invalid assignment target".to_string(),
+                        ))
+                    }
+                };
+                Ok((instruction, false))
             }
         }
-    } else if input.starts_with("==") {
-        let split = smart_split(input[2..].to_string(), ',');
-        if split.len() == 2 {
-            let left = to_expression(split[0].clone());
-            let right = to_expression(split[1].clone());
-            if let (Some(left), Some(right)) = (left, right) {
-                return Some(Expression::EQ(Box::new(left), Box::new(right)));
+    }
+
+    //`(a, b)` of a function definition
+    fn parse_params(&mut self) -> Result<Vec<String>, ParseError> {
+        self.expect(TokenKind::LParen, "`(`")?;
+        let mut args: Vec<String> = vec![];
+        while !self.eat(&TokenKind::RParen) {
+            let token = self.tokens[self.index].clone();
+            let arg = self.ident()?;
+            if args.contains(&arg) {
+                return Err(ParseError::new(
+                    token.pos,
+                    &token.text,
+                    format!("duplicate parameter `{}`", arg),
+                ));
+            }
+            args.push(arg);
+            if !self.eat(&TokenKind::Comma) {
+                self.expect(TokenKind::RParen, "`)`")?;
+                break;
             }
         }
-    } else if input.starts_with("!") {
-        let data = to_expression(input[1..].to_string());
-        if let Some(data) = data {
-            return Some(Expression::NOT(Box::new(data)));
+        Ok(args)
+    }
+
+    fn parse_if(&mut self, pos: Pos) -> Result<Instruction, ParseError> {
+        let condition = self.parse_condition()?;
+        let instruction = self.parse_body()?;
+        let mut else_instruction = vec![];
+        if self.eat(&TokenKind::Else) {
+            let token = self.tokens[self.index].clone();
+            if self.eat(&TokenKind::If) {
+                else_instruction.push(self.parse_if(token.pos)?);
+            } else {
+                else_instruction = self.parse_body()?;
+            }
         }
-    } else if let Ok(i) = input.clone().parse::<i32>() {
-        return Some(Expression::Number(i));
-    } else if input == "false" {
-        return Some(Expression::Bool(false));
-    } else if input == "true" {
-        return Some(Expression::Bool(true));
-    } else if input.starts_with("\"") && input.ends_with("\"") {
-        return Some(Expression::String(input[1..input.len() - 1].to_string()));
-    } else {
-        return Some(Expression::Variable(input));
+        Ok(Instruction::IF {
+            condition,
+            instruction,
+            else_instruction,
+            pos,
+        })
     }
-    todo!("Expression:{}", input);
-}
 
-fn smart_contain(s: String, c: char) -> bool {
-    let mut chars = s.chars();
-    let exit = vec!['"'];
-    while let Some(n) = chars.next() {
-        if n == c {
-            return true;
+    fn parse_expression(&mut self) -> Result<Expression, ParseError> {
+        self.parse_or()
+    }
+
+    //an expression directly followed by a body, so no struct literals
+    fn parse_condition(&mut self) -> Result<Expression, ParseError> {
+        let outer = std::mem::replace(&mut self.structs, false);
+        let expression = self.parse_expression();
+        self.structs = outer;
+        expression
+    }
+
+    //an expression inside brackets, where struct literals are fine again
+    fn parse_nested(&mut self) -> Result<Expression, ParseError> {
+        let outer = std::mem::replace(&mut self.structs, true);
+        let expression = self.parse_expression();
+        self.structs = outer;
+        expression
+    }
+
+    fn parse_or(&mut self) -> Result<Expression, ParseError> {
+        let mut left = self.parse_and()?;
+        while self.eat(&TokenKind::Or) {
+            let right = self.parse_and()?;
+            left = Expression::OR(Box::new(left), Box::new(right));
         }
-        if exit.contains(&n) {
-            return false;
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Expression, ParseError> {
+        let mut left = self.parse_equality()?;
+        while self.eat(&TokenKind::And) {
+            let right = self.parse_equality()?;
+            left = Expression::AND(Box::new(left), Box::new(right));
         }
+        Ok(left)
     }
-    false
-}
 
-fn smart_split(s: String, c: char) -> Vec<String> {
-    let mut string = 0;
-    let mut chars = s.chars();
-    let mut out = vec![];
-    let mut args = 0;
-    let mut current = String::new();
-    while let Some(n) = chars.next() {
-        if n == c && string == 0 && args == 0 {
-            out.push(current.clone());
-            current = String::new();
-        } else if n == '"' {
-            if string > 0 {
-                string -= 1;
+    fn parse_equality(&mut self) -> Result<Expression, ParseError> {
+        let mut left = self.parse_comparison()?;
+        loop {
+            if self.eat(&TokenKind::EQ) {
+                let right = self.parse_comparison()?;
+                left = Expression::EQ(Box::new(left), Box::new(right));
+            } else if self.eat(&TokenKind::NotEq) {
+                let right = self.parse_comparison()?;
+                left = Expression::NOT_EQ(Box::new(left), Box::new(right));
             } else {
-                string += 1;
+                return Ok(left);
             }
-        } else if n == '(' {
-            args += 1;
-        } else if n == ')' {
-            args -= 1;
-        } else {
-            current.push(n);
         }
     }
-    out.push(current.clone());
-    out
-}
-
-#[derive(Debug, Clone)]
-pub enum Block {
-    Block(Vec<Block>),
-    String(String),
-}
 
-impl Block {
-    pub fn into_vec(&self) -> Option<Vec<Block>> {
-        if let Block::Block(b) = self {
-            return Some(b.clone());
+    fn parse_comparison(&mut self) -> Result<Expression, ParseError> {
+        let mut left = self.parse_term()?;
+        loop {
+            let kind = self.peek().clone();
+            let make = match kind {
+                TokenKind::Greater => Expression::GREATER,
+                TokenKind::GreaterEq => Expression::GREATER_EQ,
+                TokenKind::Less => Expression::LESS,
+                TokenKind::LessEq => Expression::LESS_EQ,
+                _ => return Ok(left),
+            };
+            self.next();
+            let right = self.parse_term()?;
+            left = make(Box::new(left), Box::new(right));
         }
-        None
     }
-}
 
-pub fn to_block(s: &mut Chars) -> Block {
-    let mut current = vec![Block::String(String::new())];
-    while let Some(n) = s.next() {
-        match n {
-            '{' => {
-                current.push(to_block(s));
-            }
-            '}' => {
-                let current = current
-                    .into_iter()
-                    .filter(|x| {
-                        if let Block::String(s) = x {
-                            if s.len() == 0 {
-                                return false;
-                            }
-                        }
-                        true
-                    })
-                    .collect::<Vec<Block>>();
-                return Block::Block(current);
-            }
-            ';' => {
-                current.push(Block::String(String::new()));
+    fn parse_term(&mut self) -> Result<Expression, ParseError> {
+        let mut left = self.parse_factor()?;
+        loop {
+            if self.eat(&TokenKind::Plus) {
+                let right = self.parse_factor()?;
+                left = Expression::Add(Box::new(left), Box::new(right));
+            } else if self.eat(&TokenKind::Minus) {
+                let right = self.parse_factor()?;
+                left = Expression::Sub(Box::new(left), Box::new(right));
+            } else {
+                return Ok(left);
             }
-            _ => {
-                if let Block::String(s) = current.last_mut().unwrap() {
-                    s.push(n);
-                } else {
-                    current.push(Block::String(n.to_string()));
-                }
+        }
+    }
+
+    fn parse_factor(&mut self) -> Result<Expression, ParseError> {
+        let mut left = self.parse_unary()?;
+        loop {
+            if self.eat(&TokenKind::Star) {
+                let right = self.parse_unary()?;
+                left = Expression::Mul(Box::new(left), Box::new(right));
+            } else if self.eat(&TokenKind::Slash) {
+                let right = self.parse_unary()?;
+                left = Expression::Div(Box::new(left), Box::new(right));
+            } else if self.eat(&TokenKind::Percent) {
+                let right = self.parse_unary()?;
+                left = Expression::Mod(Box::new(left), Box::new(right));
+            } else {
+                return Ok(left);
             }
         }
     }
-    //remove all Block::String(String::new())
-    let current = current
-        .into_iter()
-        .filter(|x| {
-            if let Block::String(s) = x {
-                if s.len() == 0 {
-                    return false;
+
+    fn parse_unary(&mut self) -> Result<Expression, ParseError> {
+        if self.eat(&TokenKind::Not) {
+            return Ok(Expression::NOT(Box::new(self.parse_unary()?)));
+        }
+        if self.eat(&TokenKind::Minus) {
+            //fold `-5` into a literal
+            match *self.peek() {
+                TokenKind::Number(n) => {
+                    self.next();
+                    return Ok(Expression::Number((-(n as i128)) as i64));
                 }
+                TokenKind::Float(f) => {
+                    self.next();
+                    return Ok(Expression::Float(-f));
+                }
+                _ => {}
             }
-            true
-        })
-        .collect::<Vec<Block>>();
-    Block::Block(current)
-}
-
-pub fn smart_trim(s: String) -> String {
-    let mut in_string = false;
-    let mut out = String::new();
-    for n in s.chars() {
-        if n == '"' {
-            in_string = !in_string;
+            return Ok(Expression::Neg(Box::new(self.parse_unary()?)));
         }
-        if n == ' ' && !in_string {
-            continue;
+        let pos = self.tokens[self.index].pos;
+        let primary = self.parse_primary()?;
+        self.parse_postfix(primary, pos)
+    }
+
+    //calls, indexing and field access following an expression that starts at pos
+    fn parse_postfix(
+        &mut self,
+        mut expression: Expression,
+        pos: Pos,
+    ) -> Result<Expression, ParseError> {
+        loop {
+            if self.eat(&TokenKind::LParen) {
+                let args = self.parse_list(TokenKind::RParen, "`)`")?;
+                expression = match expression {
+                    Expression::Variable(name, _) => Expression::CALL(name, args, pos),
+                    callee => Expression::CALL_VALUE(Box::new(callee), args, pos),
+                };
+            } else if self.eat(&TokenKind::LBracket) {
+                let index = self.parse_nested()?;
+                self.expect(TokenKind::RBracket, "`]`")?;
+                expression = Expression::Index(Box::new(expression), Box::new(index));
+            } else if self.eat(&TokenKind::Dot) {
+                expression = Expression::Field(Box::new(expression), self.ident()?);
+            } else {
+                return Ok(expression);
+            }
         }
-        if n == '\n' && !in_string {
-            continue;
+    }
+
+    //comma separated expressions up to the closing token
+    fn parse_list(&mut self, close: TokenKind, what: &str) -> Result<Vec<Expression>, ParseError> {
+        let mut items = vec![];
+        while !self.eat(&close) {
+            items.push(self.parse_nested()?);
+            if !self.eat(&TokenKind::Comma) {
+                self.expect(close, what)?;
+                break;
+            }
         }
-        out.push(n);
+        Ok(items)
     }
-    out
-}
 
-pub fn parse_block(b: Vec<Block>) -> Vec<Instruction> {
-    let mut b = b.into_iter();
-    let mut instructions = vec![];
-    while let Some(block) = b.next() {
-        match block {
-            Block::Block(b) => {
-                instructions.extend(parse_block(b));
+    fn parse_primary(&mut self) -> Result<Expression, ParseError> {
+        let token = self.next();
+        match token.kind {
+            TokenKind::LParen => {
+                let expression = self.parse_nested()?;
+                self.expect(TokenKind::RParen, "`)`")?;
+                Ok(expression)
             }
-            Block::String(s) => {
-                if s.starts_with("print") {
-                    let s = s[5..].to_string();
-                    instructions.push(Instruction::PRINT {
-                        value: to_expression(s).unwrap(),
-                    });
-                } else if s.starts_with("return") {
-                    let s = s[6..].to_string();
-                    instructions.push(Instruction::RETURN {
-                        value: to_expression(s).unwrap(),
-                    });
-                } else if s.starts_with("def") {
-                    let data = smart_split(s[3..].to_string(), '(');
-                    if data.len() == 2 {
-                        let name = data[0].clone();
-                        let args = smart_split(data[1].clone(), ',');
-                        let args = args
-                            .iter()
-                            .map(|x| x.clone())
-                            .filter(|x| x.len() > 0)
-                            .collect::<Vec<String>>();
-                        let instructions2 = parse_block(b.next().unwrap().into_vec().unwrap());
-                        instructions.push(Instruction::FUNCTION {
-                            name,
-                            args,
-                            instruction: instructions2,
-                        })
-                    }
-                } else if s.starts_with("drop") {
-                    let s = s[4..].to_string();
-                    instructions.push(Instruction::DROP {
-                        name: s,
-                    });
-                } else if s.starts_with("let") {
-                    let data = smart_split(s[3..].to_string(), '=');
-                    if data.len() == 2 {
-                        let name = data[0].clone();
-                        let value = to_expression(data[1].clone());
-                        if let Some(value) = value {
-                            instructions.push(Instruction::SET { name, value });
+            TokenKind::Number(n) => match i64::try_from(n) {
+                Ok(n) => Ok(Expression::Number(n)),
+                Err(_) => Err(ParseError::new(
+                    token.pos,
+                    &token.text,
+                    format!("number out of range: {}", token.text),
+                )),
+            },
+            TokenKind::Float(f) => Ok(Expression::Float(f)),
+            TokenKind::True => Ok(Expression::Bool(true)),
+            TokenKind::False => Ok(Expression::Bool(false)),
+            TokenKind::String(s) => Ok(Expression::String(s)),
+            TokenKind::Template(template) => {
+                let mut parts = vec![];
+                for part in template {
+                    match part {
+                        TemplatePart::Text(s) => parts.push(Expression::String(s)),
+                        TemplatePart::Code(tokens) => {
+                            let mut parser = Parser {
+                                tokens,
+                                index: 0,
+                                structs: true,
+                            };
+                            parts.push(parser.parse_expression()?);
+                            let token = parser.next();
+                            if token.kind != TokenKind::EOF {
+                                return Err(Parser::unexpected(&token, "`}`"));
+                            }
                         }
                     }
-                } else if s.starts_with("while") {
-                    let cond = s[5..].to_string();
-                    let cond = to_expression(cond).unwrap();
-                    let instructions2 = parse_block(b.next().unwrap().into_vec().unwrap());
-                    instructions.push(Instruction::WHILE {
-                        condition: cond,
-                        instruction: instructions2,
-                    })
-                } else if s.starts_with("if") {
-                    let cond = s[2..].to_string();
-                    let cond = to_expression(cond).unwrap();
-                    let instructions2 = parse_block(b.next().unwrap().into_vec().unwrap());
-                    instructions.push(Instruction::IF {
-                        condition: cond,
-                        instruction: instructions2,
-                    })
-                } else if s.contains("=") {
-                    let data = smart_split(s, '=');
-                    if data.len() == 2 {
-                        let name = data[0].clone();
-                        let value = to_expression(data[1].clone());
-                        if let Some(value) = value {
-                            instructions.push(Instruction::ASSIGN { name, value });
+                }
+                Ok(Expression::Template(parts))
+            }
+            TokenKind::Ident(name) => {
+                if self.structs && self.eat(&TokenKind::LBrace) {
+                    let mut fields = vec![];
+                    while !self.eat(&TokenKind::RBrace) {
+                        let field_pos = self.tokens[self.index].pos;
+                        let field = self.ident()?;
+                        self.expect(TokenKind::Colon, "`:`")?;
+                        fields.push((field, self.parse_expression()?, field_pos));
+                        if !self.eat(&TokenKind::Comma) {
+                            self.expect(TokenKind::RBrace, "`}`")?;
+                            break;
                         }
                     }
-                } else {
-                    todo!("Block::String:{}", s)
+                    return Ok(Expression::Record(name, fields, token.pos));
                 }
+                Ok(Expression::Variable(name, token.pos))
+            }
+            TokenKind::Fn => {
+                let args = self.parse_params()?;
+                //the body is a statement list, so struct literals are allowed again
+                let outer = std::mem::replace(&mut self.structs, true);
+                let instruction = self.parse_body();
+                self.structs = outer;
+                Ok(Expression::Lambda(args, instruction?))
             }
+            TokenKind::LBrace => {
+                let mut entries = vec![];
+                while !self.eat(&TokenKind::RBrace) {
+                    let key = self.parse_nested()?;
+                    self.expect(TokenKind::Colon, "`:`")?;
+                    entries.push((key, self.parse_nested()?));
+                    if !self.eat(&TokenKind::Comma) {
+                        self.expect(TokenKind::RBrace, "`}`")?;
+                        break;
+                    }
+                }
+                Ok(Expression::Map(entries))
+            }
+            TokenKind::LBracket => Ok(Expression::List(
+                self.parse_list(TokenKind::RBracket, "`]`")?,
+            )),
+            _ => Err(Parser::unexpected(&token, "expression")),
         }
     }
-    instructions
 }
 
-pub fn parse(s: String) -> Vec<Instruction> {
-    let s = smart_trim(s);
-    let mut chars = s.chars();
-    let block = vec![to_block(&mut chars)];
-    parse_block(block)
+pub fn to_expression(input: String) -> Option<Expression> {
+    let mut parser = Parser::new(&input).ok()?;
+    let expression = parser.parse_expression().ok()?;
+    if *parser.peek() != TokenKind::EOF {
+        return None;
+    }
+    Some(expression)
+}
+
+pub fn parse(s: String) -> Result<Vec<Instruction>, ParseError> {
+    Parser::new(&s)?.parse_program()
 }